@@ -0,0 +1,225 @@
+/*
+* No-limit betting rounds of a hand.
+* The functions work on the stored PokerTable and leave the dealing to the contract: when a betting
+* round is over they tell the caller whether the next street has to be revealed, the remaining players
+* are all-in (or the river was played) and the hand is waiting for its showdown, or everybody else
+* folded and the last player took the pot.
+*/
+use cosmwasm_std::Uint128;
+
+use crate::error::ContractError;
+use crate::state::{ActionRecord, Betting, Currency, GameState, Player, PlayerAction, PokerTable};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Progress {
+    NextPlayer,
+    NextStreet,
+    RoundsOver, // no more betting is possible, the hand goes to showdown
+    Uncontested { winner: usize, pot: Uint128, uncalled: Uint128 }, // the pot includes the winner's uncalled chips
+}
+
+pub fn validate_blinds(small_blind: Uint128, big_blind: Uint128) -> Result<(), ContractError> {
+    if small_blind.is_zero() || small_blind > big_blind {
+        return Err(ContractError::InvalidBlinds { small_blind, big_blind });
+    }
+    Ok(())
+}
+
+/*
+ * Heads-up the button posts the small blind and acts first before the flop,
+ * otherwise the two seats left of the button post the blinds.
+ * A player short of a blind is all-in, the others still have to call the full big blind.
+ */
+pub fn post_blinds(table: &mut PokerTable, small_blind: Uint128, big_blind: Uint128, button: usize, escrow: Option<Currency>) {
+    let count = table.players.len();
+    let button = button % count;
+    let (small, big) = if count == 2 {
+        (button, (button + 1) % count)
+    } else {
+        ((button + 1) % count, (button + 2) % count)
+    };
+
+    let mut betting = Betting {
+        small_blind,
+        big_blind,
+        button,
+        current_bet: big_blind,
+        min_raise: big_blind,
+        to_act: None,
+        escrow,
+        actions: vec![],
+    };
+    for (index, blind, action) in [
+        (small, small_blind, PlayerAction::SmallBlind),
+        (big, big_blind, PlayerAction::BigBlind),
+    ] {
+        let player = &mut table.players[index];
+        let amount = commit(player, blind);
+        betting.actions.push(ActionRecord {
+            player_id: player.player_id,
+            game_state: table.game_state.clone(),
+            action,
+            amount,
+        });
+    }
+    betting.to_act = next_to_act(&table.players, &betting, big);
+    table.betting = Some(betting);
+}
+
+/*
+ * Bet is the amount put in on an unopened street, Raise is the total street bet raised to.
+ * An all-in below a full raise must still be called by the others, but the next raise
+ * is measured from the last full one and the players who already acted can only call or fold.
+ */
+pub fn apply_action(
+    table: &mut PokerTable,
+    table_id: u32,
+    player_index: usize,
+    action: PlayerAction,
+    amount: Uint128,
+) -> Result<Progress, ContractError> {
+    let betting = table
+        .betting
+        .as_mut()
+        .ok_or(ContractError::BettingDisabled { table_id })?;
+    let player = &mut table.players[player_index];
+    if !table.game_state.is_street() || betting.to_act != Some(player_index) {
+        return Err(ContractError::NotPlayerTurn {
+            table_id,
+            player: player.player_id.to_string(),
+        });
+    }
+
+    let invalid_action = ContractError::InvalidAction {
+        table_id,
+        player: player.player_id.to_string(),
+        action: action.clone(),
+    };
+    let to_call = betting.current_bet.saturating_sub(player.street_bet);
+    let chips = match action {
+        PlayerAction::Fold => {
+            player.folded = true;
+            Uint128::zero()
+        }
+        PlayerAction::Check if to_call.is_zero() => Uint128::zero(),
+        PlayerAction::Call if !to_call.is_zero() => commit(player, to_call),
+        PlayerAction::Bet if betting.current_bet.is_zero() => {
+            validate_amount(table_id, amount, betting.big_blind, player.stack)?;
+            commit(player, amount)
+        }
+        PlayerAction::Raise if !betting.current_bet.is_zero() && !player.acted => {
+            let min = betting.current_bet + betting.min_raise;
+            validate_amount(table_id, amount, min, player.street_bet + player.stack)?;
+            commit(player, amount - player.street_bet)
+        }
+        PlayerAction::AllIn if !player.acted || player.stack <= to_call => commit(player, player.stack),
+        _ => return Err(invalid_action),
+    };
+    player.acted = true;
+    let player_id = player.player_id;
+    let street_bet = player.street_bet;
+
+    if street_bet > betting.current_bet {
+        let raise = street_bet - betting.current_bet;
+        betting.current_bet = street_bet;
+        // only a full raise reopens the betting for the players who already acted
+        if raise >= betting.min_raise {
+            betting.min_raise = raise;
+            for (index, other) in table.players.iter_mut().enumerate() {
+                if index != player_index {
+                    other.acted = false;
+                }
+            }
+        }
+    }
+    betting.actions.push(ActionRecord {
+        player_id,
+        game_state: table.game_state.clone(),
+        action,
+        amount: chips,
+    });
+
+    let in_hand: Vec<usize> = (0..table.players.len())
+        .filter(|&index| !table.players[index].folded)
+        .collect();
+    if let [winner] = in_hand[..] {
+        let pot = pot(&table.players);
+        let called = table
+            .players
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != winner)
+            .map(|(_, player)| player.total_bet)
+            .max()
+            .unwrap_or_default();
+        let uncalled = table.players[winner].total_bet.saturating_sub(called);
+        table.players[winner].stack += pot;
+        betting.to_act = None;
+        return Ok(Progress::Uncontested { winner, pot, uncalled });
+    }
+
+    betting.to_act = next_to_act(&table.players, betting, player_index);
+    if betting.to_act.is_some() {
+        Ok(Progress::NextPlayer)
+    } else if table.game_state == GameState::River || able_to_act(&table.players) < 2 {
+        Ok(Progress::RoundsOver)
+    } else {
+        Ok(Progress::NextStreet)
+    }
+}
+
+/*
+ * Resets the street bets once the next street is revealed, the first player left of the button acts.
+ */
+pub fn start_street(table: &mut PokerTable) {
+    if let Some(betting) = table.betting.as_mut() {
+        for player in table.players.iter_mut() {
+            player.street_bet = Uint128::zero();
+            player.acted = false;
+        }
+        betting.current_bet = Uint128::zero();
+        betting.min_raise = betting.big_blind;
+        betting.to_act = next_to_act(&table.players, betting, betting.button);
+    }
+}
+
+pub fn pot(players: &[Player]) -> Uint128 {
+    players.iter().map(|player| player.total_bet).sum()
+}
+
+fn commit(player: &mut Player, amount: Uint128) -> Uint128 {
+    let amount = amount.min(player.stack);
+    player.stack -= amount;
+    player.street_bet += amount;
+    player.total_bet += amount;
+    if player.stack.is_zero() {
+        player.all_in = true;
+    }
+    amount
+}
+
+fn validate_amount(table_id: u32, amount: Uint128, min: Uint128, max: Uint128) -> Result<(), ContractError> {
+    if amount < min || amount > max {
+        return Err(ContractError::InvalidBetAmount { table_id, amount, min, max });
+    }
+    Ok(())
+}
+
+fn able_to_act(players: &[Player]) -> usize {
+    players.iter().filter(|player| !player.folded && !player.all_in).count()
+}
+
+/*
+ * The next player after `from` who has not acted since the last raise or still has to call it.
+ * A player left alone with chips only acts when facing a bet, there is nobody to bet against.
+ */
+fn next_to_act(players: &[Player], betting: &Betting, from: usize) -> Option<usize> {
+    let able = able_to_act(players);
+    (1..=players.len())
+        .map(|offset| (from + offset) % players.len())
+        .find(|&index| {
+            let player = &players[index];
+            let facing_bet = player.street_bet < betting.current_bet;
+            !player.folded && !player.all_in && (facing_bet || (!player.acted && able > 1))
+        })
+}
@@ -1,45 +1,67 @@
+use std::cmp::Reverse;
 use std::collections::HashSet;
 
 use cosmwasm_std::{
-    entry_point, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
+    coins, entry_point, from_binary, to_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, Event, MessageInfo,
+    Response, StdError, StdResult, Storage, Timestamp, Uint128,
 };
-use secret_toolkit_crypto::hkdf_sha_512;
+use rand_core::RngCore;
+use secret_toolkit_crypto::{hkdf_sha_512, ContractPrng};
 use secret_toolkit_permit::{validate, Permit};
-use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
 
+use crate::betting::{self, Progress};
 use crate::error::ContractError;
+use crate::evaluator;
 use crate::msg::{
-    CommunityCardsResponse, ExecuteMsg, InstantiateMsg, LastHandLogResponse, QueryMsg, QueryWithPermit, ResponsePayload, ShowdownPlayer, ShowdownResponse, StartGamePlayer, StartGameResponse
+    ActionResponse, BalanceResponse, BettingConfig, ConfigResponse, HandHistoryResponse, TableInfo, TablesResponse, BoardResult, CommunityCardsResponse, Contribution, ExecuteMsg, InstantiateMsg, LastHandLogResponse, LowHand, QueryMsg, QueryWithPermit, RankedHand, ResponsePayload, SecretShare, ShowdownPlayer, ShowdownResponse, StartGamePlayer, StartGameResponse, Payout, PotResult, ReceiveMsg, TokenContractInfo
 };
+use crate::pot;
+use crate::shamir;
+use crate::snip20;
 use crate::state::{
-    load_table, save_table, Card, CommunityCards, Config, Deck, Flop, GameState,
-    Player, PokerTable, River, Turn, CONFIG_KEY, COUNTER_KEY, PREFIX_REVOKED_PERMITS,
+    archive_hand, delete_table, hand_history, last_archived_hand, load_balance, load_table, load_token_held, table_ids, table_operator, save_balance, save_table, Card, CommunityCards, Config, Currency, Deck, DeckType, Flop, GameState,
+    GameVariant, Pause, Player, PlayerAction, PokerTable, RakeConfig, River, SecretSharing, TokenContract, Turn, CONFIG_KEY, COUNTER_KEY, OFF_CHAIN_RAKE, PREFIX_REVOKED_PERMITS, TOKEN_HELD, TREASURY,
 };
 
 const MIN_PLAYERS: usize = 2;
-const MAX_PLAYERS: usize = 9;
+const COMMUNITY_CARDS: usize = 5;
+const BURN_CARDS: usize = 3;
+const DEFAULT_PAGE_LIMIT: u32 = 10;
+const MAX_PAGE_LIMIT: u32 = 30;
 const COMMUNITY_CARD_PHASES: usize = 3;
 const SECRET_LENGTH: usize = 64;
+const DECK_SALT_LENGTH: usize = 32;
 const RANDOM_SEED_SIZE: usize = 16;
 const RESPONSE_KEY: &str = "response";
+const NATIVE_DENOM: &str = "uscrt";
 
 mod helpers {
     use super::*;
 
-    pub fn generate_random_number(env: &Env, counter: &mut u128) -> StdResult<u64> {
+    pub fn generate_random_bytes(env: &Env, counter: &mut u128) -> StdResult<Vec<u8>> {
         let secret = hkdf_sha_512(
             &Some(vec![0u8; SECRET_LENGTH]),
-            &env.block.random.as_ref().unwrap(),
+            env.block.random.as_ref().unwrap(),
             &counter.to_le_bytes(),
             SECRET_LENGTH,
         )?;
 
         *counter += 1;
+        Ok(secret)
+    }
+
+    pub fn generate_random_number(env: &Env, counter: &mut u128) -> StdResult<u64> {
+        let secret = generate_random_bytes(env, counter)?;
         Ok(u64::from_le_bytes(secret[..8].try_into().unwrap()))
     }
 
+    pub fn generate_salt(env: &Env, counter: &mut u128) -> StdResult<Vec<u8>> {
+        let secret = generate_random_bytes(env, counter)?;
+        Ok(secret[..DECK_SALT_LENGTH].to_vec())
+    }
+
     pub fn additive_secret_sharing(
         env: &Env,
         players: usize,
@@ -59,20 +81,90 @@ mod helpers {
         Ok(shares)
     }
 
-    pub fn shuffle_deck(deck: &mut Deck, seed: u64) {
-        let mut rng = Sha256::new();
+    pub fn shamir_secret_sharing(
+        env: &Env,
+        players: usize,
+        threshold: u32,
+        secret: u64,
+        counter: &mut u128,
+    ) -> StdResult<Vec<u64>> {
+        let coefficients = (1..threshold)
+            .map(|_| generate_random_number(env, counter))
+            .collect::<StdResult<Vec<u64>>>()?;
+
+        Ok(shamir::split(secret, &coefficients, players as u32))
+    }
+
+    /*
+     * Recombines the shares sent by the players into the community secret of the table.
+     * Each share must come from a distinct player of the hand (index in 1..=players).
+     */
+    pub fn recombine_shares(
+        secret_sharing: &SecretSharing,
+        player_count: usize,
+        shares: &[SecretShare],
+    ) -> Option<u64> {
+        let unique_indexes: HashSet<_> = shares.iter().map(|s| s.index).collect();
+        if unique_indexes.len() != shares.len()
+            || shares.iter().any(|s| s.index == 0 || s.index as usize > player_count)
+        {
+            return None;
+        }
+
+        match secret_sharing {
+            SecretSharing::Additive if shares.len() == player_count => {
+                Some(shares.iter().map(|s| s.share).fold(0u64, u64::wrapping_add))
+            }
+            SecretSharing::Shamir { threshold } if shares.len() >= *threshold as usize => {
+                shamir::reconstruct(&shares.iter().map(|s| (s.index, s.share)).collect::<Vec<_>>())
+            }
+            _ => None,
+        }
+    }
+
+    /*
+     * Returns a value uniformly distributed in [0, bound). Draws falling in the incomplete
+     * last block of u32::MAX are rejected, otherwise the lowest values would be favoured (modulo bias).
+     */
+    pub fn uniform_index(rng: &mut ContractPrng, bound: u32) -> u32 {
+        let limit = u32::MAX - u32::MAX % bound;
+        loop {
+            let value = rng.next_u32();
+            if value < limit {
+                return value % bound;
+            }
+        }
+    }
+
+    /*
+     * Fisher-Yates shuffle driven by a ChaCha20 stream seeded with the full 64-byte seed,
+     * so every permutation of the deck is reachable and equally likely.
+     */
+    pub fn shuffle_deck(deck: &mut Deck, seed: &[u8]) {
+        let mut rng = ContractPrng::new(seed, &[]);
         let mut deck_len = deck.cards.len();
 
         while deck_len > 1 {
+            let random_index = uniform_index(&mut rng, deck_len as u32) as usize;
             deck_len -= 1;
-            rng.update(&seed.to_le_bytes());
-            rng.update(&(deck_len as u64).to_le_bytes());
+            deck.cards.swap(deck_len, random_index);
+        }
+    }
 
-            let hash = rng.finalize_reset();
-            let random_value = u64::from_le_bytes(hash[..8].try_into().unwrap());
-            let random_index = (random_value as usize) % (deck_len + 1);
+    pub fn validate_rake(rake: RakeConfig) -> Result<RakeConfig, ContractError> {
+        if rake.basis_points > 10_000 {
+            return Err(ContractError::InvalidRake {
+                basis_points: rake.basis_points,
+            });
+        }
+        Ok(rake)
+    }
 
-            deck.cards.swap(deck_len, random_index);
+    // no flop no drop: the hands ended before the flop are not raked
+    pub fn hand_rake(rake: Option<&RakeConfig>, pot: Uint128, flop_dealt: bool) -> Uint128 {
+        match rake {
+            Some(rake) if flop_dealt => pot::rake(pot, rake.basis_points, rake.cap),
+            _ => Uint128::zero(),
         }
     }
 }
@@ -87,6 +179,30 @@ mod state_utils {
     ) -> Result<PokerTable, ContractError> {
         load_table(storage, table_id).ok_or(ContractError::TableNotFound { table_id })
     }
+
+    // a table whose hand can still be dealt and played
+    pub fn load_live_table(
+        storage: &dyn cosmwasm_std::Storage,
+        table_id: u32,
+    ) -> Result<PokerTable, ContractError> {
+        let table = load_table_or_error(storage, table_id)?;
+        if table.void_reason.is_some() {
+            return Err(ContractError::HandVoided {
+                table_id,
+                hand_ref: table.hand_ref,
+            });
+        }
+        Ok(table)
+    }
+
+    /*
+     * A new table can be created by any operator, an existing one is run by its operator or the owner.
+     * A closed table stays with its operator when it reopens.
+     */
+    pub fn runs_table(storage: &dyn cosmwasm_std::Storage, config: &Config, sender: &Addr, table_id: u32) -> bool {
+        config.is_operator(sender)
+            && table_operator(storage, table_id).is_none_or(|operator| *sender == config.owner || operator == *sender)
+    }
 }
 
 
@@ -119,6 +235,11 @@ mod query_handlers {
             
            to_binary(&serialized?)
             }
+            QueryWithPermit::Balance { table_id } => to_binary(&BalanceResponse {
+                table_id,
+                balance: load_balance(deps.storage, table_id, &Currency::Token, &viewer),
+                native_balance: load_balance(deps.storage, table_id, &Currency::Native, &viewer),
+            }),
         }
     }
 
@@ -133,7 +254,7 @@ mod query_handlers {
         table
             .players
             .iter()
-            .find(|p| p.public_key == pub_key)
+            .find(|p| p.address.as_str() == pub_key)
             .cloned()
             .ok_or(StdError::generic_err("No player found"))
             .map(|player| PlayerDataResponse {
@@ -141,17 +262,78 @@ mod query_handlers {
                 hand_ref: table.hand_ref,
                 hand: player.hand,
                 hand_secret: player.hand_secret.to_string(),
+                share_index: player.share_index,
                 flop_secret_share: player.flop_secret_share.to_string(),
                 turn_secret_share: player.turn_secret_share.to_string(),
                 river_secret_share: player.river_secret_share.to_string(),
             })
     }
 
+    pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+        let config = CONFIG_KEY.load(deps.storage)?;
+        Ok(ConfigResponse {
+            owner: config.owner,
+            pending_owner: config.pending_owner,
+            operators: config.operators,
+            pause: config.pause,
+        })
+    }
+
+    /*
+     * Public summary of the tables, nothing about the cards or the secrets of their hands.
+     */
+    pub fn query_tables(deps: Deps, start_after: Option<u32>, limit: Option<u32>) -> StdResult<TablesResponse> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+        let table_ids = table_ids(deps.storage, start_after, limit)?
+            .ok_or_else(|| StdError::generic_err(format!("Table {} not found", start_after.unwrap_or_default())))?;
+
+        let tables = table_ids
+            .into_iter()
+            .filter_map(|table_id| load_table(deps.storage, table_id).map(|table| (table_id, table)))
+            .map(|(table_id, table)| TableInfo {
+                table_id,
+                hand_ref: table.hand_ref,
+                player_count: table.players.len() as u32,
+                last_activity: table.last_activity(),
+                game_state: table.game_state,
+            })
+            .collect();
+        Ok(TablesResponse { tables })
+    }
+
+    pub fn query_hand_history(
+        deps: Deps,
+        table_id: u32,
+        start_after: Option<u32>,
+        limit: Option<u32>,
+    ) -> StdResult<HandHistoryResponse> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+        let history = hand_history(table_id);
+        // the hands are archived by increasing hand_ref, a binary search finds the first one after start_after
+        let (mut start, mut end) = (0, history.get_len(deps.storage)? as usize);
+        if let Some(start_after) = start_after {
+            while start < end {
+                let middle = (start + end) / 2;
+                match history.iter_keys(deps.storage)?.nth(middle).transpose()? {
+                    Some(hand_ref) if hand_ref <= start_after => start = middle + 1,
+                    _ => end = middle,
+                }
+            }
+        }
+        let hands = history
+            .iter(deps.storage)?
+            .skip(start)
+            .take(limit)
+            .map(|hand| hand.map(|(_, hand_log)| hand_log))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(HandHistoryResponse { table_id, hands })
+    }
+
     pub fn query_community_cards(
         deps: Deps,
         table_id: u32,
         game_state: GameState,
-        secret_key: u64,
+        shares: Vec<SecretShare>,
     ) -> StdResult<CommunityCardsResponse> {
         let table =
             load_table(deps.storage, table_id).ok_or(StdError::generic_err("No table found"))?;
@@ -172,7 +354,9 @@ mod query_handlers {
             _ => return Err(StdError::generic_err("Invalid game state")),
         };
 
-        if stored_key != secret_key {
+        let secret_key =
+            helpers::recombine_shares(&table.secret_sharing, table.players.len(), &shares);
+        if secret_key != Some(stored_key) {
             return Err(StdError::generic_err("Invalid viewing key"));
         }
 
@@ -225,7 +409,7 @@ mod query_handlers {
                     .players
                     .iter()
                     .find(|player| &player.hand_secret == secret)
-                    .map(|player| (player.player_id.clone(), player.hand.clone()))
+                    .map(|player| (player.player_id, player.hand.clone()))
                     .ok_or_else(|| StdError::generic_err("Player not found"))
             })
             .collect::<Result<Vec<_>, _>>()?;
@@ -235,44 +419,99 @@ mod query_handlers {
             hand_ref: table.hand_ref,
             players_cards,
             community_cards: Some(community_cards),
+            boards: vec![], // all-in runouts, results and pots are only published by the Showdown execute
+            results: vec![],
+            pots: vec![],
+            uncalled: vec![],
+            payouts: vec![],
+            rake: Uint128::zero(),
         })
     }
 }
 
 
 mod execute_handlers {
-    use super::{state_utils::load_table_or_error, *};
+    use super::{state_utils::{load_live_table, load_table_or_error}, *};
 
+    #[allow(clippy::too_many_arguments)]
     pub fn handle_start_game(
         deps: DepsMut,
         env: Env,
+        sender: Addr,
         table_id: u32,
         hand_ref: u32,
         players_info: Vec<StartGamePlayer>,
         prev_hand_showdown_players: Vec<Uuid>,
+        threshold: Option<u32>,
+        game_variant: GameVariant,
+        deck_type: DeckType,
+        burn_cards: bool,
+        betting: Option<BettingConfig>,
+        force: bool,
     ) -> Result<Response, ContractError> {
-        validate_players(&players_info)?;
-        let previous_hand_log = create_previous_hand_log(deps.as_ref(), table_id, prev_hand_showdown_players)?;
+        let previous = load_table(deps.storage, table_id);
+        if let Some(previous) = &previous {
+            validate_next_hand(previous, table_id, hand_ref, force)?;
+            refund_escrow(deps.storage, table_id, previous)?;
+        } else if let Some(last_hand_ref) = last_archived_hand(deps.storage, table_id)? {
+            // a closed table reopens after the hands of its history
+            validate_hand_ref(table_id, hand_ref, last_hand_ref)?;
+        }
+        let operator = table_operator(deps.storage, table_id).unwrap_or(sender);
         let mut counter = COUNTER_KEY.load(deps.storage)?;
-        let mut deck = initialize_deck(&env, &mut counter)?;
-        let player_cards = distribute_player_cards(&mut deck, &players_info);
+        let mut deck = initialize_deck(&env, &mut counter, &deck_type)?;
+        validate_players(&players_info, &game_variant, deck.cards.len(), burn_cards)?;
+        let addresses = players_info
+            .iter()
+            .map(|player| deps.api.addr_validate(&player.public_key))
+            .collect::<StdResult<Vec<Addr>>>()?;
+        if let Some(config) = &betting {
+            validate_stakes(config, &players_info)?;
+            if let Some(currency) = &config.escrow {
+                debit_escrow(deps.storage, table_id, currency, &players_info)?;
+            }
+        }
+        let secret_sharing = secret_sharing_scheme(threshold, players_info.len())?;
+        let previous_hand_log = create_previous_hand_log(deps.storage, table_id, prev_hand_showdown_players)?;
+        if let Some(hand_log) = &previous_hand_log {
+            archive_hand(deps.storage, table_id, hand_log)?;
+        }
+        let shuffled_deck = deck.clone();
+        let deck_salt = Binary::from(helpers::generate_salt(&env, &mut counter)?);
+        let player_cards = distribute_player_cards(&mut deck, &players_info, game_variant.hole_cards());
         let mut secrets = Vec::with_capacity(COMMUNITY_CARD_PHASES);
         let community_cards =
-            generate_community_cards(&env, &mut counter, &mut secrets, &mut deck, players_info.len())?;
+            generate_community_cards(&env, &mut counter, &mut secrets, &mut deck, players_info.len(), &secret_sharing, burn_cards)?;
         let players = create_players(
             players_info,
             player_cards,
+            addresses,
             &secrets,
             &env,
             &mut counter,
         )?;
 
-        let table = PokerTable {
+        let mut table = PokerTable {
+            operator,
             hand_ref,
+            game_state: GameState::PreFlop,
             players,
             community_cards,
+            deck: shuffled_deck,
+            deck_salt,
+            secret_sharing,
+            game_variant,
+            deck_type,
+            extra_runs: vec![],
+            betting: None,
+            rake: Uint128::zero(),
+            started_at: env.block.time,
             showdown_retrieved_at: None,
+            void_reason: None,
         };
+        if let Some(config) = betting {
+            betting::post_blinds(&mut table, config.small_blind, config.big_blind, config.button as usize, config.escrow);
+        }
 
         save_table(deps.storage, table_id, &table)?;
         COUNTER_KEY.save(deps.storage, &counter)?;
@@ -280,13 +519,65 @@ mod execute_handlers {
         create_start_game_response(
             table_id,
             hand_ref,
-            &table.players,
+            &table,
             previous_hand_log,
         )
     }
 
-    fn validate_players(players_info: &[StartGamePlayer]) -> Result<(), ContractError> {
-        if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&players_info.len()) {
+    /*
+     * Hands of a table are numbered upwards and a new one only replaces a finished hand, unless it is forced.
+     */
+    fn validate_next_hand(previous: &PokerTable, table_id: u32, hand_ref: u32, force: bool) -> Result<(), ContractError> {
+        validate_hand_ref(table_id, hand_ref, previous.hand_ref)?;
+        if previous.game_state != GameState::Closed && !force {
+            return Err(ContractError::PreviousHandInProgress {
+                table_id,
+                hand_ref: previous.hand_ref,
+                game_state: previous.game_state.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_hand_ref(table_id: u32, hand_ref: u32, previous: u32) -> Result<(), ContractError> {
+        if hand_ref <= previous {
+            return Err(ContractError::HandRefNotIncreasing {
+                table_id,
+                hand_ref,
+                previous,
+            });
+        }
+        Ok(())
+    }
+
+    /*
+     * Gives the players of an unfinished escrow hand back everything they brought to it.
+     */
+    fn refund_escrow(storage: &mut dyn Storage, table_id: u32, table: &PokerTable) -> Result<(), ContractError> {
+        if !holds_escrow(table) {
+            return Ok(());
+        }
+        let currency = table.betting.as_ref().and_then(|betting| betting.escrow.as_ref()).unwrap();
+        for player in table.players.iter() {
+            let balance = load_balance(storage, table_id, currency, &player.public_key);
+            save_balance(storage, table_id, currency, &player.public_key, balance + player.stack + player.total_bet)?;
+        }
+        Ok(())
+    }
+
+    /*
+     * The table size is bounded by the deck: every player gets the variant's hole cards
+     * and the board still needs its five community cards (plus the burn cards if enabled).
+     */
+    fn validate_players(
+        players_info: &[StartGamePlayer],
+        game_variant: &GameVariant,
+        deck_size: usize,
+        burn_cards: bool,
+    ) -> Result<(), ContractError> {
+        let board_cards = COMMUNITY_CARDS + if burn_cards { BURN_CARDS } else { 0 };
+        let max_players = deck_size.saturating_sub(board_cards) / game_variant.hole_cards();
+        if !(MIN_PLAYERS..=max_players).contains(&players_info.len()) {
             return Err(ContractError::InvalidPlayerCount {
                 count: players_info.len(),
             });
@@ -301,28 +592,79 @@ mod execute_handlers {
         Ok(())
     }
 
-    fn initialize_deck(env: &Env, counter: &mut u128) -> Result<Deck, ContractError> {
-        let mut deck = Deck::new();
-        let seed = helpers::generate_random_number(env, counter)?;
-        helpers::shuffle_deck(&mut deck, seed);
+    fn validate_stakes(config: &BettingConfig, players_info: &[StartGamePlayer]) -> Result<(), ContractError> {
+        betting::validate_blinds(config.small_blind, config.big_blind)?;
+        if let Some(player) = players_info.iter().find(|player| player.stack.is_zero()) {
+            return Err(ContractError::EmptyStack {
+                player: player.player_id.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /*
+     * Moves every player's stack from their escrowed balance to the hand.
+     */
+    fn debit_escrow(
+        storage: &mut dyn Storage,
+        table_id: u32,
+        currency: &Currency,
+        players_info: &[StartGamePlayer],
+    ) -> Result<(), ContractError> {
+        if *currency == Currency::Token && CONFIG_KEY.load(storage)?.accepted_token.is_none() {
+            return Err(ContractError::NoAcceptedToken {});
+        }
+        let balances: Vec<Uint128> = players_info
+            .iter()
+            .map(|player| load_balance(storage, table_id, currency, &player.public_key))
+            .collect();
+        for (player, balance) in players_info.iter().zip(balances.iter()) {
+            if player.stack > *balance {
+                return Err(ContractError::InsufficientBalance {
+                    table_id,
+                    player: player.public_key.clone(),
+                    balance: *balance,
+                });
+            }
+        }
+        for (player, balance) in players_info.iter().zip(balances) {
+            save_balance(storage, table_id, currency, &player.public_key, balance - player.stack)?;
+        }
+        Ok(())
+    }
+
+    fn secret_sharing_scheme(
+        threshold: Option<u32>,
+        players: usize,
+    ) -> Result<SecretSharing, ContractError> {
+        match threshold {
+            None => Ok(SecretSharing::Additive),
+            Some(threshold) if (2..=players).contains(&(threshold as usize)) => {
+                Ok(SecretSharing::Shamir { threshold })
+            }
+            Some(threshold) => Err(ContractError::InvalidThreshold { threshold, players }),
+        }
+    }
+
+    fn initialize_deck(
+        env: &Env,
+        counter: &mut u128,
+        deck_type: &DeckType,
+    ) -> Result<Deck, ContractError> {
+        let mut deck = Deck::from_type(deck_type);
+        let seed = helpers::generate_random_bytes(env, counter)?;
+        helpers::shuffle_deck(&mut deck, &seed);
         Ok(deck)
     }
 
     fn distribute_player_cards(
         deck: &mut Deck,
         players: &[StartGamePlayer],
+        hole_cards: usize,
     ) -> Vec<(String, Vec<Card>)> {
         players
             .iter()
-            .map(|player| {
-                (
-                    player.public_key.clone(),
-                    vec![
-                        deck.cards.pop().unwrap().clone(),
-                        deck.cards.pop().unwrap().clone(),
-                    ],
-                )
-            })
+            .map(|player| (player.public_key.clone(), collect_cards(deck, hole_cards)))
             .collect()
     }
 
@@ -332,40 +674,71 @@ mod execute_handlers {
         secrets: &mut Vec<(u64, Vec<u64>)>,
         deck: &mut Deck,
         player_count: usize,
+        secret_sharing: &SecretSharing,
+        burn_cards: bool,
     ) -> Result<CommunityCards, ContractError> {
 
         for _ in 0..COMMUNITY_CARD_PHASES {
             let secret = helpers::generate_random_number(env, counter)?;
-            let shares = helpers::additive_secret_sharing(env, player_count, secret, counter)?;
+            let (secret, shares) = match secret_sharing {
+                SecretSharing::Additive => (
+                    secret,
+                    helpers::additive_secret_sharing(env, player_count, secret, counter)?,
+                ),
+                SecretSharing::Shamir { threshold } => {
+                    let secret = secret % shamir::PRIME;
+                    (
+                        secret,
+                        helpers::shamir_secret_sharing(env, player_count, *threshold, secret, counter)?,
+                    )
+                }
+            };
             secrets.push((secret, shares));
         }
 
+        let mut burned = Vec::new();
+        let flop_cards = deal_street(deck, 3, burn_cards, &mut burned);
+        let turn_card = deal_street(deck, 1, burn_cards, &mut burned).remove(0);
+        let river_card = deal_street(deck, 1, burn_cards, &mut burned).remove(0);
+
         Ok(CommunityCards {
             flop: Flop {
-                cards: collect_cards(deck, 3),
+                cards: flop_cards,
                 secret: secrets[0].0,
                 retrieved_at: None,
+                submitted_shares: vec![],
             },
             turn: Turn {
-                card: deck.cards.pop().unwrap().clone(),
+                card: turn_card,
                 secret: secrets[1].0,
                 retrieved_at: None,
+                submitted_shares: vec![],
             },
             river: River {
-                card: deck.cards.pop().unwrap().clone(),
+                card: river_card,
                 secret: secrets[2].0,
                 retrieved_at: None,
+                submitted_shares: vec![],
             },
+            burned,
         })
     }
 
+    fn deal_street(deck: &mut Deck, count: usize, burn_cards: bool, burned: &mut Vec<Card>) -> Vec<Card> {
+        if burn_cards {
+            burned.extend(collect_cards(deck, 1));
+        }
+        collect_cards(deck, count)
+    }
+
     fn collect_cards(deck: &mut Deck, count: usize) -> Vec<Card> {
-        (0..count).filter_map(|_| Some(deck.cards.pop().unwrap().clone())).collect()
+        (0..count).map(|_| deck.cards.pop().unwrap()).collect()
     }
 
     fn create_players(
         players_info: Vec<StartGamePlayer>,
         player_cards: Vec<(String, Vec<Card>)>,
+        addresses: Vec<Addr>,
         secrets: &[(u64, Vec<u64>)],
         env: &Env,
         counter: &mut u128,
@@ -374,17 +747,26 @@ mod execute_handlers {
         players_info
             .into_iter()
             .zip(player_cards)
+            .zip(addresses)
             .enumerate()
-            .map(|(i, (info, (_, cards)))| {
+            .map(|(i, ((info, (_, cards)), address))| {
                 Ok(Player {
                     username: info.username,
                     player_id: info.player_id,
                     public_key: info.public_key,
+                    address,
                     hand: cards,
                     hand_secret: helpers::generate_random_number(env, counter)?,
+                    share_index: i as u32 + 1,
                     flop_secret_share: secrets[0].1[i],
                     turn_secret_share: secrets[1].1[i],
                     river_secret_share: secrets[2].1[i],
+                    stack: info.stack,
+                    street_bet: Uint128::zero(),
+                    total_bet: Uint128::zero(),
+                    folded: false,
+                    all_in: false,
+                    acted: false,
                 })
             })
             .collect()
@@ -393,13 +775,16 @@ mod execute_handlers {
     fn create_start_game_response(
         table_id: u32,
         hand_ref: u32,
-        players: &[Player],
+        table: &PokerTable,
         previous_hand_log: Option<LastHandLogResponse>,
     ) -> Result<Response, ContractError> {
         let response = ResponsePayload::StartGame(StartGameResponse {
             table_id,
             hand_ref,
-            players: players.iter().map(|p| p.username.clone()).collect(),
+            players: table.players.iter().map(|p| p.username.clone()).collect(),
+            game_variant: table.game_variant.clone(),
+            deck_type: table.deck_type.clone(),
+            deck_commitment: Binary::from(table.deck.commitment(table.deck_salt.as_slice())),
         });
         let mut res = create_plaintext_response(RESPONSE_KEY.to_string(), response)?;
 
@@ -409,24 +794,33 @@ mod execute_handlers {
         Ok(res)
     }
 
-    fn create_previous_hand_log(deps: Deps, table_id: u32, showdown_player_ids: Vec<Uuid>) -> Result<Option<LastHandLogResponse>, ContractError> {
-        let table = load_table(deps.storage, table_id);
-        let previous_hand_log = if table.is_some() {
-            let table = table.unwrap();
-            
+    fn create_previous_hand_log(storage: &dyn Storage, table_id: u32, showdown_player_ids: Vec<Uuid>) -> Result<Option<LastHandLogResponse>, ContractError> {
+        let previous_hand_log = if let Some(table) = load_table(storage, table_id) {
             Some(LastHandLogResponse {
+                hand_ref: table.hand_ref,
                 showdown_players: showdown_player_ids.iter().map(|player_id| {
-                    let player = table.players.iter().find(|player| &player.player_id == player_id).unwrap();
-                    ShowdownPlayer {
+                    let player = table.players.iter().find(|player| &player.player_id == player_id).ok_or_else(|| {
+                        ContractError::PlayerNotFound {
+                            table_id,
+                            player: player_id.to_string(),
+                        }
+                    })?;
+                    Ok(ShowdownPlayer {
                         username: player.username.clone(),
                         hand: player.hand.iter().map(|card| card.to_string()).collect(),
-                    }
-                }).collect(),
+                    })
+                }).collect::<Result<_, ContractError>>()?,
                 community_cards: [table.community_cards.flop.cards.iter().map(|card| card.to_string()).collect(), vec![table.community_cards.turn.card.to_string()], vec![table.community_cards.river.card.to_string()]].concat(),
                 flop_retrieved_at: table.community_cards.flop.retrieved_at,
                 turn_retrieved_at: table.community_cards.turn.retrieved_at,
                 river_retrieved_at: table.community_cards.river.retrieved_at,
                 showdown_retrieved_at: table.showdown_retrieved_at,
+                deck_salt: table.deck_salt,
+                deck: Binary::from(table.deck.to_bytes()),
+                deck_type: table.deck_type,
+                burned_cards: table.community_cards.burned.iter().map(|card| card.to_string()).collect(),
+                extra_runs: table.extra_runs.iter().map(|board| board.iter().map(|card| card.to_string()).collect()).collect(),
+                void_reason: table.void_reason,
             })
         } else {
             None
@@ -458,41 +852,177 @@ mod execute_handlers {
         table_id: u32,
         game_state: GameState,
     ) -> Result<Response, ContractError> {
-        let mut table = load_table_or_error(deps.storage, table_id)?;
-        
+        let mut table = load_live_table(deps.storage, table_id)?;
+        validate_next_street(&table, table_id, &game_state, "distribute_community_cards")?;
+
+        let cards = reveal_street(&mut table, &env, &game_state);
+        save_table(deps.storage, table_id, &table)?;
+
+        let response = ResponsePayload::CommunityCards(CommunityCardsResponse {
+            table_id,
+            hand_ref: table.hand_ref,
+            game_state,
+            community_cards: cards,
+        });
+
+        create_plaintext_response(RESPONSE_KEY.to_string(), response)
+    }
+
+    fn validate_next_street(
+        table: &PokerTable,
+        table_id: u32,
+        game_state: &GameState,
+        method: &str,
+    ) -> Result<(), ContractError> {
+        ensure_betting_closed(table, table_id)?;
+        // only a street can be revealed, and none once the all-in boards are dealt: the hand goes to showdown
+        let expected = table
+            .game_state
+            .next()
+            .filter(|next| table.extra_runs.is_empty() && next.is_street());
+
+        if expected.as_ref() != Some(game_state) {
+            return Err(ContractError::GameStateError {
+                method: method.to_string(),
+                table_id,
+                expected,
+                game_state: Some(game_state.clone()),
+            });
+        }
+        Ok(())
+    }
+
+    /*
+     * On betting tables the streets are revealed by the action closing the betting round,
+     * the hand can only be moved forward by hand once the remaining players are all-in or the river was played.
+     */
+    fn ensure_betting_closed(table: &PokerTable, table_id: u32) -> Result<(), ContractError> {
+        match &table.betting {
+            Some(betting) if betting.to_act.is_some() => Err(ContractError::BettingRoundOpen {
+                table_id,
+                game_state: table.game_state.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn reveal_street(table: &mut PokerTable, env: &Env, game_state: &GameState) -> Vec<Card> {
         let cards = match game_state {
             GameState::Flop => {
                 table.community_cards.flop.retrieved_at = Some(env.block.time);
-                Some(table.community_cards.flop.cards.clone())
+                table.community_cards.flop.cards.clone()
             }
             GameState::Turn => {
                 table.community_cards.turn.retrieved_at = Some(env.block.time);
-                Some(vec![table.community_cards.turn.card.clone()])
+                vec![table.community_cards.turn.card.clone()]
             }
             GameState::River => {
                 table.community_cards.river.retrieved_at = Some(env.block.time);
-                Some(vec![table.community_cards.river.card.clone()])
-            }
-            _ => {
-                return Err(ContractError::GameStateError {
-                    method: "distribute_community_cards".to_string(),
-                    table_id,
-                    game_state: Some(game_state),
-                })
+                vec![table.community_cards.river.card.clone()]
             }
+            _ => unreachable!("street validated by validate_next_street"),
         };
 
-        
+        table.game_state = game_state.clone();
+        cards
+    }
+
+    /*
+     * Players sign their own messages, the sender is matched against the addresses seated at StartGame.
+     */
+    fn seat_of(table: &PokerTable, table_id: u32, sender: &Addr) -> Result<usize, ContractError> {
+        table
+            .players
+            .iter()
+            .position(|player| player.address == *sender)
+            .ok_or_else(|| ContractError::PlayerNotFound {
+                table_id,
+                player: sender.to_string(),
+            })
+    }
+
+    /*
+     * Collects the phase share of the sender, which must be one of the players of the hand.
+     * Once enough shares are in (all of them for additive tables, `threshold` for Shamir tables)
+     * the contract recombines the secret itself and reveals the street, so no off-chain
+     * coordinator ever has to see the shares.
+     */
+    pub fn handle_submit_share(
+        deps: DepsMut,
+        env: Env,
+        sender: Addr,
+        table_id: u32,
+        phase: GameState,
+        share: u64,
+    ) -> Result<Response, ContractError> {
+        let mut table = load_live_table(deps.storage, table_id)?;
+        validate_next_street(&table, table_id, &phase, "submit_share")?;
+
+        let player = &table.players[seat_of(&table, table_id, &sender)?];
+        let dealt_share = match phase {
+            GameState::Flop => player.flop_secret_share,
+            GameState::Turn => player.turn_secret_share,
+            _ => player.river_secret_share,
+        };
+        if share != dealt_share {
+            return Err(ContractError::InvalidSecretShare {
+                table_id,
+                player: sender.to_string(),
+                game_state: phase,
+            });
+        }
+
+        let share_index = player.share_index;
+        let player_count = table.players.len();
+        let submitted_shares = table.community_cards.submitted_shares_mut(&phase).unwrap();
+        if submitted_shares.iter().any(|(index, _)| *index == share_index) {
+            return Err(ContractError::DuplicateSecretShare {
+                table_id,
+                player: sender.to_string(),
+                game_state: phase,
+            });
+        }
+        submitted_shares.push((share_index, share));
+
+        let shares: Vec<SecretShare> = submitted_shares
+            .iter()
+            .map(|&(index, share)| SecretShare { index, share })
+            .collect();
+        let submitted_count = shares.len();
+        let secret = helpers::recombine_shares(&table.secret_sharing, player_count, &shares);
+
+        if secret.is_none() || secret != table.community_cards.secret(&phase) {
+            save_table(deps.storage, table_id, &table)?;
+            return Ok(Response::new()
+                .add_attribute_plaintext("submitted_shares", submitted_count.to_string()));
+        }
+
+        let cards = reveal_street(&mut table, &env, &phase);
         save_table(deps.storage, table_id, &table)?;
 
+        let event = Event::new("community_cards_unlocked")
+            .add_attribute_plaintext("table_id", table_id.to_string())
+            .add_attribute_plaintext("hand_ref", table.hand_ref.to_string())
+            .add_attribute_plaintext("game_state", serialize_game_state(&phase)?);
+
         let response = ResponsePayload::CommunityCards(CommunityCardsResponse {
             table_id,
             hand_ref: table.hand_ref,
-            game_state,
-            community_cards: cards.unwrap(),
+            game_state: phase,
+            community_cards: cards,
         });
 
-        create_plaintext_response(RESPONSE_KEY.to_string(), response)
+        Ok(create_plaintext_response(RESPONSE_KEY.to_string(), response)?
+            .add_attribute_plaintext("submitted_shares", submitted_count.to_string())
+            .add_event(event))
+    }
+
+    fn serialize_game_state(game_state: &GameState) -> Result<String, ContractError> {
+        serde_json_wasm::to_string(game_state)
+            .map(|json| json.trim_matches('"').to_string())
+            .map_err(|e| ContractError::SerializationFailed {
+                error: e.to_string(),
+            })
     }
 
     pub fn handle_showdown(
@@ -501,9 +1031,23 @@ mod execute_handlers {
         table_id: u32,
         game_state: GameState,
         showdown_player_ids: Vec<Uuid>,
+        contributions: Vec<Contribution>,
+        pot: Option<Uint128>,
     ) -> Result<Response, ContractError> {
-        let mut table = load_table(deps.storage, table_id)
-            .ok_or_else(|| ContractError::TableNotFound { table_id })?;
+        let mut table = load_live_table(deps.storage, table_id)?;
+        let config = CONFIG_KEY.load(deps.storage)?;
+
+        if !table.game_state.is_street() || table.game_state != game_state {
+            // the street the hand is on, nothing once it reached its showdown
+            let expected = table.game_state.is_street().then_some(table.game_state);
+            return Err(ContractError::GameStateError {
+                method: "showdown".to_string(),
+                table_id,
+                expected,
+                game_state: Some(game_state),
+            });
+        }
+        ensure_betting_closed(&table, table_id)?;
 
         let mut player_hands: Vec<(Uuid, Vec<Card>)> = Vec::new();
 
@@ -514,7 +1058,7 @@ mod execute_handlers {
                 .find(|player| &player.player_id == player_id);
 
             if let Some(player) = players {
-                player_hands.push((player.player_id.clone(), player.hand.clone()));
+                player_hands.push((player.player_id, player.hand.clone()));
             } else {
                 return Err(ContractError::PlayerNotFound {
                     table_id,
@@ -523,60 +1067,915 @@ mod execute_handlers {
             }
         }
 
+        let results = if player_hands.is_empty() {
+            vec![]
+        } else {
+            full_boards(&table, &game_state)
+                .into_iter()
+                .map(|board| rank_board(&table, table_id, board, &player_hands))
+                .collect::<Result<_, _>>()?
+        };
+        // a showdown between players deals the whole board, even from preflop
+        let flop_dealt = game_state != GameState::PreFlop || player_hands.len() > 1;
+        let rake_for = |pot| helpers::hand_rake(config.rake.as_ref(), pot, flop_dealt);
+        // nothing shown on a betting table: settle_pots gives the committed chips back, they were never won
+        let settlement = if player_hands.is_empty() && table.betting.is_none() {
+            let pot = pot.unwrap_or_else(|| contributions.iter().map(|contribution| contribution.amount).sum());
+            Settlement {
+                rake: rake_for(pot),
+                ..Settlement::default()
+            }
+        } else {
+            settle_pots(&mut table, table_id, &player_hands, &results, contributions, pot, rake_for)?
+        };
+        table.rake = settlement.rake;
+        credit_treasury(deps.storage, &table)?;
+        let transfers = release_escrow(deps.storage, table_id, &table, &settlement.winnings)?;
+        let community_cards = handle_all_in_showdown(&table.community_cards, game_state);
+        let boards = community_cards
+            .iter()
+            .cloned()
+            .chain(table.extra_runs.iter().cloned())
+            .collect();
+
         let response = ResponsePayload::Showdown(ShowdownResponse {
             table_id,
             hand_ref: table.hand_ref,
             players_cards: player_hands,
-            community_cards: handle_all_in_showdown(&table.community_cards, game_state),
+            community_cards,
+            boards,
+            results,
+            pots: settlement.pots,
+            uncalled: settlement.uncalled,
+            payouts: settlement.payouts,
+            rake: settlement.rake,
         });
 
-        
+        // the pots are settled along with the showdown, which closes the hand
+        table.game_state = GameState::Closed;
         table.showdown_retrieved_at = Some(env.block.time);
         save_table(deps.storage, table_id, &table)?;
 
-        create_plaintext_response(RESPONSE_KEY.to_string(), response)
+        Ok(create_plaintext_response(RESPONSE_KEY.to_string(), response)?.add_messages(transfers))
     }
 
-    fn handle_all_in_showdown(
-        community_cards: &CommunityCards,
-        game_state: GameState,
-    ) -> Option<Vec<Card>> {
-        match game_state {
-            GameState::PreFlop => {
-                let mut cards = community_cards.flop.cards.clone();
-                cards.push(community_cards.turn.card.clone());
-                cards.push(community_cards.river.card.clone());
-                Some(cards)
-            }
-            GameState::Flop => Some(vec![
-                community_cards.turn.card.clone(),
-                community_cards.river.card.clone(),
-            ]),
-            GameState::Turn => Some(vec![community_cards.river.card.clone()]),
-            _ => return None,
-        }
+    #[derive(Default)]
+    struct Settlement {
+        pots: Vec<PotResult>,
+        uncalled: Vec<Payout>,
+        winnings: Vec<Payout>, // won from the pots, the only chips an escrow hand sends out
+        payouts: Vec<Payout>,
+        rake: Uint128,
     }
-}
 
+    /*
+     * Builds the main and side pots from the contributions, takes the rake out of them and awards them
+     * to the best shown hands. Betting tables use the chips committed on-chain and credit the winners' stacks,
+     * the odd chips go to the first winners left of the button (left of the first seat without a button).
+     * Without betting the rake is computed from the supplied pot size when there is one, it can't exceed
+     * the pots built from the contributions though, when there are any. Betting tables rake their own pots,
+     * and return every chip when no hand is shown.
+     */
+    fn settle_pots(
+        table: &mut PokerTable,
+        table_id: u32,
+        player_hands: &[(Uuid, Vec<Card>)],
+        results: &[BoardResult],
+        contributions: Vec<Contribution>,
+        supplied_pot: Option<Uint128>,
+        rake_for: impl Fn(Uint128) -> Uint128,
+    ) -> Result<Settlement, ContractError> {
+        let first_seat = table.betting.as_ref().map_or(0, |betting| betting.button + 1);
+        let seats: Vec<&Player> = (0..table.players.len())
+            .map(|offset| &table.players[(first_seat + offset) % table.players.len()])
+            .collect();
 
-#[entry_point]
-pub fn instantiate(deps: DepsMut, env: Env, info: MessageInfo, _msg: InstantiateMsg,) -> Result<Response, StdError> {
-    let config = Config {
-        owner: info.sender,
-        contract_address: env.contract.address.clone(),
-    };
+        let chips: Vec<(Uuid, Uint128)> = if table.betting.is_some() {
+            seats.iter().map(|player| (player.player_id, player.total_bet)).collect()
+        } else {
+            if let Some(contribution) = contributions
+                .iter()
+                .find(|contribution| seats.iter().all(|player| player.player_id != contribution.player_id))
+            {
+                return Err(ContractError::PlayerNotFound {
+                    table_id,
+                    player: contribution.player_id.to_string(),
+                });
+            }
+            seats
+                .iter()
+                .filter_map(|player| {
+                    contributions
+                        .iter()
+                        .find(|contribution| contribution.player_id == player.player_id)
+                        .map(|contribution| (player.player_id, contribution.amount))
+                })
+                .collect()
+        };
+        let eligible: Vec<Uuid> = seats
+            .iter()
+            .filter(|player| !player.folded && player_hands.iter().any(|(id, _)| *id == player.player_id))
+            .map(|player| player.player_id)
+            .collect();
 
-    let counter = init_counter(&env)?;
+        let (mut pots, uncalled) = pot::build_pots(&chips, &eligible);
+        let pots_size: Uint128 = pots.iter().map(|pot| pot.amount).sum();
+        let pot_size = match supplied_pot {
+            Some(pot) if table.betting.is_none() => pot,
+            _ => pots_size,
+        };
+        // without contributions there is no pot to take it from, the supplied pot is raked off-chain
+        let rake = if pots.is_empty() {
+            rake_for(pot_size)
+        } else {
+            pot::take_rake(&mut pots, rake_for(pot_size))
+        };
+        let pot_payouts = pot::resolve(&pots, results.len(), |board, eligible| {
+            board_winners(&results[board], eligible, &table.deck_type)
+        });
 
-    CONFIG_KEY.save(deps.storage, &config)?;
-    COUNTER_KEY.save(deps.storage, &counter)?;
+        let merge = |amounts: Vec<&(Uuid, Uint128)>| -> Vec<Payout> {
+            let mut merged: Vec<Payout> = Vec::new();
+            for (player_id, amount) in amounts {
+                match merged.iter_mut().find(|payout| payout.player_id == *player_id) {
+                    Some(payout) => payout.amount += *amount,
+                    None => merged.push(Payout { player_id: *player_id, amount: *amount }),
+                }
+            }
+            merged
+        };
+        let winnings = merge(pot_payouts.iter().flatten().collect());
+        let payouts = merge(pot_payouts.iter().flatten().chain(uncalled.iter()).collect());
+        if table.betting.is_some() {
+            for payout in payouts.iter() {
+                if let Some(player) = table.players.iter_mut().find(|player| player.player_id == payout.player_id) {
+                    player.stack += payout.amount;
+                }
+            }
+        }
 
-    Ok(Response::default())
-}
+        let to_payouts = |amounts: &[(Uuid, Uint128)]| -> Vec<Payout> {
+            amounts
+                .iter()
+                .map(|&(player_id, amount)| Payout { player_id, amount })
+                .collect()
+        };
+        let pots = pots
+            .into_iter()
+            .zip(pot_payouts.iter())
+            .map(|(pot, payouts)| PotResult {
+                amount: pot.amount,
+                eligible: pot.eligible,
+                payouts: to_payouts(payouts),
+            })
+            .collect();
+        Ok(Settlement {
+            pots,
+            uncalled: to_payouts(&uncalled),
+            winnings,
+            payouts,
+            rake,
+        })
+    }
 
-fn init_counter(env: &Env) -> StdResult<u128> {
-    let seed = env
-        .block
+    /*
+     * The escrow hands hold the funds the rake is taken from and credit the treasury of their currency,
+     * the rake of the other hands is collected off-chain and only added up.
+     */
+    fn credit_treasury(storage: &mut dyn Storage, table: &PokerTable) -> Result<(), ContractError> {
+        if table.rake.is_zero() {
+            return Ok(());
+        }
+        match table.betting.as_ref().and_then(|betting| betting.escrow.as_ref()) {
+            Some(currency) => {
+                let treasury = TREASURY.get(storage, currency).unwrap_or_default();
+                TREASURY.insert(storage, currency, &(treasury + table.rake))?;
+            }
+            None => {
+                let off_chain = OFF_CHAIN_RAKE.may_load(storage)?.unwrap_or_default();
+                OFF_CHAIN_RAKE.save(storage, &(off_chain + table.rake))?;
+            }
+        }
+        Ok(())
+    }
+
+    /*
+     * At the end of an escrow hand the pot winnings are sent to the winners (Transfer for the token, BankMsg::Send
+     * for uscrt) and the chips left in front of every player, uncalled and refunded ones included, go back
+     * to their escrowed balance.
+     */
+    fn release_escrow(
+        storage: &mut dyn Storage,
+        table_id: u32,
+        table: &PokerTable,
+        winnings: &[Payout],
+    ) -> Result<Vec<CosmosMsg>, ContractError> {
+        let Some(currency) = table.betting.as_ref().and_then(|betting| betting.escrow.clone()) else {
+            return Ok(vec![]);
+        };
+        let token = CONFIG_KEY.load(storage)?.accepted_token;
+
+        let mut transfers = Vec::new();
+        for player in table.players.iter() {
+            let won = winnings
+                .iter()
+                .find(|payout| payout.player_id == player.player_id)
+                .map(|payout| payout.amount)
+                .unwrap_or_default();
+            let left = player.stack - won;
+            if !left.is_zero() {
+                let balance = load_balance(storage, table_id, &currency, &player.public_key);
+                save_balance(storage, table_id, &currency, &player.public_key, balance + left)?;
+            }
+            if !won.is_zero() {
+                transfers.push(payout_msg(storage, &currency, token.as_ref(), player.public_key.clone(), won)?);
+            }
+        }
+        Ok(transfers)
+    }
+
+    /*
+     * Transfer out of the contract, the tokens leaving are taken off TOKEN_HELD.
+     */
+    fn payout_msg(
+        storage: &mut dyn Storage,
+        currency: &Currency,
+        token: Option<&TokenContract>,
+        recipient: String,
+        amount: Uint128,
+    ) -> Result<CosmosMsg, ContractError> {
+        match currency {
+            Currency::Token => {
+                let token = token.ok_or(ContractError::NoAcceptedToken {})?;
+                let held = load_token_held(storage)?;
+                TOKEN_HELD.save(storage, &held.saturating_sub(amount))?;
+                Ok(snip20::transfer_msg(token, recipient, amount)?)
+            }
+            Currency::Native => Ok(BankMsg::Send {
+                to_address: recipient,
+                amount: coins(amount.u128(), NATIVE_DENOM),
+            }
+            .into()),
+        }
+    }
+
+    /*
+     * High and low winners of a board among the players eligible for a pot.
+     */
+    fn board_winners(
+        result: &BoardResult,
+        eligible: &[Uuid],
+        deck_type: &DeckType,
+    ) -> (Vec<Uuid>, Vec<Uuid>) {
+        let hands: Vec<&RankedHand> = result
+            .hands
+            .iter()
+            .filter(|hand| eligible.contains(&hand.player_id))
+            .collect();
+        let ranks: Vec<evaluator::HandRank> = hands
+            .iter()
+            .map(|hand| evaluator::HandRank {
+                category: hand.category,
+                kickers: hand.kickers.clone(),
+            })
+            .collect();
+        let strengths: Vec<_> = ranks.iter().map(|rank| evaluator::strength(rank, deck_type)).collect();
+        let high = evaluator::winners(&strengths)
+            .into_iter()
+            .map(|i| hands[i].player_id)
+            .collect();
+
+        let low_hands: Vec<&LowHand> = result
+            .low_hands
+            .iter()
+            .filter(|hand| eligible.contains(&hand.player_id))
+            .collect();
+        let lows: Vec<_> = low_hands.iter().map(|hand| Reverse(&hand.low)).collect();
+        let low = evaluator::winners(&lows)
+            .into_iter()
+            .map(|i| low_hands[i].player_id)
+            .collect();
+        (high, low)
+    }
+
+    /*
+     * The five-card boards of the hand: the regular one, then one per extra run, completing
+     * the streets that were known when the players went all-in.
+     */
+    fn full_boards(table: &PokerTable, game_state: &GameState) -> Vec<Vec<Card>> {
+        let community_cards = &table.community_cards;
+        let mut board = community_cards.flop.cards.clone();
+        board.push(community_cards.turn.card.clone());
+        board.push(community_cards.river.card.clone());
+
+        let known = match game_state {
+            GameState::PreFlop => 0,
+            GameState::Flop => 3,
+            _ => 4,
+        };
+        let extra_boards: Vec<Vec<Card>> = table
+            .extra_runs
+            .iter()
+            .map(|run| [&board[..known], run.as_slice()].concat())
+            .collect();
+
+        std::iter::once(board).chain(extra_boards).collect()
+    }
+
+    /*
+     * Ranks the shown hands on one board with the table's variant and deck. A player whose cards can't make
+     * a five card hand is rejected rather than left out of the pots.
+     */
+    fn rank_board(
+        table: &PokerTable,
+        table_id: u32,
+        board: Vec<Card>,
+        player_hands: &[(Uuid, Vec<Card>)],
+    ) -> Result<BoardResult, ContractError> {
+        let game_variant = &table.game_variant;
+        let ranked: Vec<(evaluator::HandRank, RankedHand)> = player_hands
+            .iter()
+            .map(|(player_id, hand)| {
+                let (rank, best_hand) = match game_variant {
+                    GameVariant::HoldEm => evaluator::best_hand(hand, &board, &table.deck_type),
+                    GameVariant::Omaha4 | GameVariant::Omaha5 | GameVariant::Omaha8 => {
+                        evaluator::best_omaha_hand(hand, &board, &table.deck_type)
+                    }
+                }
+                .ok_or_else(|| ContractError::InvalidHand {
+                    table_id,
+                    player: player_id.to_string(),
+                })?;
+                let ranked_hand = RankedHand {
+                    player_id: *player_id,
+                    category: rank.category,
+                    kickers: rank.kickers.clone(),
+                    best_hand,
+                };
+                Ok((rank, ranked_hand))
+            })
+            .collect::<Result<_, ContractError>>()?;
+
+        let ranks: Vec<_> = ranked
+            .iter()
+            .map(|(rank, _)| evaluator::strength(rank, &table.deck_type))
+            .collect();
+        let winners = evaluator::winners(&ranks)
+            .into_iter()
+            .map(|i| ranked[i].1.player_id)
+            .collect();
+
+        let low_hands: Vec<LowHand> = if game_variant.is_hi_lo() {
+            player_hands
+                .iter()
+                .filter_map(|(player_id, hand)| {
+                    evaluator::best_omaha_low(hand, &board).map(|(low, best_hand)| LowHand {
+                        player_id: *player_id,
+                        low,
+                        best_hand,
+                    })
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+        let lows: Vec<_> = low_hands.iter().map(|hand| Reverse(&hand.low)).collect();
+        let low_winners = evaluator::winners(&lows)
+            .into_iter()
+            .map(|i| low_hands[i].player_id)
+            .collect();
+
+        Ok(BoardResult {
+            board,
+            hands: ranked.into_iter().map(|(_, hand)| hand).collect(),
+            winners,
+            low_hands,
+            low_winners,
+        })
+    }
+
+    /*
+     * Deals `runs - 1` extra boards for an all-in on `game_state`, from the cards left in the deck once the
+     * hand was dealt. They are popped from the end like the rest of the hand (burning before each street if
+     * the table burns), so they can be checked against the deck revealed in the next hand's log.
+     */
+    pub fn handle_run_it_multiple(
+        deps: DepsMut,
+        table_id: u32,
+        game_state: GameState,
+        runs: u32,
+    ) -> Result<Response, ContractError> {
+        let mut table = load_live_table(deps.storage, table_id)?;
+
+        let streets: &[usize] = match game_state {
+            GameState::PreFlop => &[3, 1, 1],
+            GameState::Flop => &[1, 1],
+            _ => &[1],
+        };
+        if !matches!(game_state, GameState::PreFlop | GameState::Flop | GameState::Turn)
+            || table.game_state != game_state
+            || !table.extra_runs.is_empty()
+        {
+            return Err(ContractError::GameStateError {
+                method: "run_it_multiple".to_string(),
+                table_id,
+                expected: Some(table.game_state),
+                game_state: Some(game_state),
+            });
+        }
+        ensure_betting_closed(&table, table_id)?;
+
+        let burn_cards = !table.community_cards.burned.is_empty();
+        let cards_per_run = streets.iter().sum::<usize>() + if burn_cards { streets.len() } else { 0 };
+        let dealt = table.players.len() * table.game_variant.hole_cards()
+            + COMMUNITY_CARDS
+            + table.community_cards.burned.len();
+        let mut deck = Deck {
+            cards: table.deck.cards[..table.deck.cards.len() - dealt].to_vec(),
+        };
+        if runs < 2 || (runs as usize - 1) * cards_per_run > deck.cards.len() {
+            return Err(ContractError::InvalidRunCount { table_id, runs });
+        }
+
+        for _ in 1..runs {
+            let mut burned = Vec::new();
+            let board = streets
+                .iter()
+                .flat_map(|&count| deal_street(&mut deck, count, burn_cards, &mut burned))
+                .collect();
+            table.community_cards.burned.extend(burned);
+            table.extra_runs.push(board);
+        }
+        save_table(deps.storage, table_id, &table)?;
+
+        Ok(Response::new().add_attribute_plaintext("runs", runs.to_string()))
+    }
+
+    /*
+     * Applies a betting action of `player_id`. When it closes the betting round the next street is
+     * revealed in the same response, and when everybody else folded the pot goes to the last player
+     * and the hand ends without a showdown.
+     */
+    pub fn handle_player_action(
+        deps: DepsMut,
+        env: Env,
+        sender: Addr,
+        table_id: u32,
+        action: PlayerAction,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        let mut table = load_live_table(deps.storage, table_id)?;
+        let player_index = seat_of(&table, table_id, &sender)?;
+        let player_id = table.players[player_index].player_id;
+
+        let progress = betting::apply_action(&mut table, table_id, player_index, action.clone(), amount)?;
+        let mut community_cards = vec![];
+        let mut winner = None;
+        let mut transfers = vec![];
+        match progress {
+            Progress::NextStreet => {
+                let next = table.game_state.next().expect("streets before the river have a next one");
+                community_cards = reveal_street(&mut table, &env, &next);
+                betting::start_street(&mut table);
+            }
+            Progress::Uncontested { winner: index, pot, uncalled } => {
+                let player_id = table.players[index].player_id;
+                let flop_dealt = table.game_state != GameState::PreFlop;
+                // the uncalled chips go back to the winner, only the called pot is raked
+                let rake = CONFIG_KEY.load(deps.storage)?.rake;
+                table.rake = helpers::hand_rake(rake.as_ref(), pot - uncalled, flop_dealt);
+                table.players[index].stack -= table.rake;
+                credit_treasury(deps.storage, &table)?;
+                winner = Some(player_id);
+                let winnings = Payout { player_id, amount: pot - uncalled - table.rake };
+                transfers = release_escrow(deps.storage, table_id, &table, &[winnings])?;
+                table.game_state = GameState::Closed;
+                table.showdown_retrieved_at = Some(env.block.time);
+            }
+            Progress::NextPlayer | Progress::RoundsOver => {}
+        }
+        save_table(deps.storage, table_id, &table)?;
+
+        let betting = table.betting.as_ref().expect("betting checked by apply_action");
+        let response = ResponsePayload::Action(ActionResponse {
+            table_id,
+            hand_ref: table.hand_ref,
+            player_id,
+            action,
+            amount: betting.actions.last().map(|record| record.amount).unwrap_or_default(),
+            pot: betting::pot(&table.players),
+            game_state: table.game_state.clone(),
+            to_act: betting.to_act.map(|index| table.players[index].player_id),
+            community_cards,
+            winner,
+            rake: table.rake,
+        });
+
+        Ok(create_plaintext_response(RESPONSE_KEY.to_string(), response)?.add_messages(transfers))
+    }
+
+    /*
+     * Switching tokens does not convert the escrowed balances, it is only meant for an empty escrow.
+     */
+    pub fn handle_set_accepted_token(
+        deps: DepsMut,
+        env: Env,
+        token: Option<TokenContractInfo>,
+    ) -> Result<Response, ContractError> {
+        let mut config = CONFIG_KEY.load(deps.storage)?;
+        let token = token
+            .map(|token| validate_token(deps.as_ref(), token))
+            .transpose()?;
+        // the balances and the treasury are counted in the current token, they can't be moved to another one
+        let held = load_token_held(deps.storage)?;
+        let same_address = config.accepted_token.as_ref().map(|token| &token.address)
+            == token.as_ref().map(|token| &token.address);
+        if !held.is_zero() && !same_address {
+            return Err(ContractError::TokenHeld { amount: held });
+        }
+        config.accepted_token = token;
+        CONFIG_KEY.save(deps.storage, &config)?;
+
+        let mut res = Response::new();
+        if let Some(token) = &config.accepted_token {
+            res = res.add_message(snip20::register_receive_msg(token, env.contract.code_hash)?);
+        }
+        Ok(res)
+    }
+
+    pub fn validate_token(deps: Deps, token: TokenContractInfo) -> StdResult<TokenContract> {
+        Ok(TokenContract {
+            address: deps.api.addr_validate(&token.address)?,
+            code_hash: token.code_hash,
+        })
+    }
+
+    /*
+     * SNIP-20 Send callback: the sender is the token contract and `from` the player buying in.
+     */
+    pub fn handle_receive(
+        deps: DepsMut,
+        token_address: Addr,
+        from: String,
+        amount: Uint128,
+        msg: Option<Binary>,
+    ) -> Result<Response, ContractError> {
+        let config = CONFIG_KEY.load(deps.storage)?;
+        if config.accepted_token.map(|token| token.address) != Some(token_address.clone()) {
+            return Err(ContractError::UnsupportedToken {
+                token: token_address.to_string(),
+            });
+        }
+        let msg = msg.ok_or(ContractError::InvalidReceiveMsg {})?;
+        let from = deps.api.addr_validate(&from)?;
+
+        match from_binary(&msg)? {
+            ReceiveMsg::BuyIn { table_id } => {
+                let balance = load_balance(deps.storage, table_id, &Currency::Token, from.as_str()) + amount;
+                save_balance(deps.storage, table_id, &Currency::Token, from.as_str(), balance)?;
+                let held = load_token_held(deps.storage)? + amount;
+                TOKEN_HELD.save(deps.storage, &held)?;
+                Ok(Response::new().add_attribute("balance", balance.to_string()))
+            }
+        }
+    }
+
+    pub fn handle_withdraw(deps: DepsMut, sender: Addr, table_id: u32) -> Result<Response, ContractError> {
+        let token = CONFIG_KEY.load(deps.storage)?.accepted_token;
+        let balance = take_balance(deps.storage, table_id, &Currency::Token, &sender)?;
+
+        let transfer = payout_msg(deps.storage, &Currency::Token, token.as_ref(), sender.to_string(), balance)?;
+        Ok(Response::new().add_message(transfer))
+    }
+
+    pub fn handle_join_table(
+        deps: DepsMut,
+        sender: Addr,
+        table_id: u32,
+        funds: Vec<Coin>,
+    ) -> Result<Response, ContractError> {
+        let amount = match &funds[..] {
+            [coin] if coin.denom == NATIVE_DENOM && !coin.amount.is_zero() => coin.amount,
+            _ => {
+                return Err(ContractError::InvalidFunds {
+                    denom: NATIVE_DENOM.to_string(),
+                })
+            }
+        };
+        let balance = load_balance(deps.storage, table_id, &Currency::Native, sender.as_str()) + amount;
+        save_balance(deps.storage, table_id, &Currency::Native, sender.as_str(), balance)?;
+
+        Ok(Response::new().add_attribute("balance", balance.to_string()))
+    }
+
+    /*
+     * Sends the native balance back. The chips of a hand in progress are only returned
+     * to the balance at the end of the hand, so a player in a hand has to wait for it.
+     */
+    pub fn handle_leave_table(deps: DepsMut, sender: Addr, table_id: u32) -> Result<Response, ContractError> {
+        if let Some(table) = load_table(deps.storage, table_id) {
+            let in_hand = table.game_state != GameState::Closed
+                && table.betting.as_ref().is_some_and(|betting| betting.escrow == Some(Currency::Native))
+                && table.players.iter().any(|player| player.address == sender);
+            if in_hand {
+                return Err(ContractError::PlayerInHand {
+                    table_id,
+                    player: sender.to_string(),
+                });
+            }
+        }
+        let balance = take_balance(deps.storage, table_id, &Currency::Native, &sender)?;
+
+        let transfer = payout_msg(deps.storage, &Currency::Native, None, sender.to_string(), balance)?;
+        Ok(Response::new().add_message(transfer))
+    }
+
+    /*
+     * Ends the hand without a winner. Its log shows every player's hole cards next to the deck and its salt,
+     * so anyone can check the deal against the commitment published at StartGame.
+     */
+    pub fn handle_void_hand(deps: DepsMut, table_id: u32, reason: String) -> Result<Response, ContractError> {
+        let mut table = load_live_table(deps.storage, table_id)?;
+        if !table.game_state.is_street() {
+            return Err(ContractError::HandFinished {
+                table_id,
+                hand_ref: table.hand_ref,
+            });
+        }
+        refund_escrow(deps.storage, table_id, &table)?;
+        table.game_state = GameState::Closed;
+        if let Some(betting) = table.betting.as_mut() {
+            betting.to_act = None;
+        }
+        table.void_reason = Some(reason.clone());
+        save_table(deps.storage, table_id, &table)?;
+        // the log is built from the saved table, so the archived one carries the reason
+        let player_ids = table.players.iter().map(|player| player.player_id).collect();
+        let hand_log = create_previous_hand_log(deps.storage, table_id, player_ids)?
+            .ok_or(ContractError::TableNotFound { table_id })?;
+        archive_hand(deps.storage, table_id, &hand_log)?;
+
+        let event = Event::new("hand_voided")
+            .add_attribute_plaintext("table_id", table_id.to_string())
+            .add_attribute_plaintext("hand_ref", table.hand_ref.to_string())
+            .add_attribute_plaintext("reason", reason)
+            .add_attribute_plaintext("hand_log", serialize_response(ResponsePayload::LastHand(hand_log))?);
+        Ok(Response::new().add_event(event))
+    }
+
+    /*
+     * Archives the final hand of the table, publishes its log and deletes the table with the secrets of the hand.
+     */
+    pub fn handle_close_table(
+        deps: DepsMut,
+        table_id: u32,
+        showdown_player_ids: Vec<Uuid>,
+    ) -> Result<Response, ContractError> {
+        let table = load_table_or_error(deps.storage, table_id)?;
+        if holds_escrow(&table) {
+            return Err(ContractError::HandInProgress {
+                table_id,
+                hand_ref: table.hand_ref,
+            });
+        }
+        let hand_log = close_table(deps.storage, table_id, showdown_player_ids)?;
+
+        let event = Event::new("table_closed")
+            .add_attribute_plaintext("table_id", table_id.to_string())
+            .add_attribute_plaintext("hand_ref", table.hand_ref.to_string())
+            .add_attribute_plaintext("hand_log", serialize_response(ResponsePayload::LastHand(hand_log))?);
+        Ok(Response::new().add_event(event))
+    }
+
+    /*
+     * Looks at up to `limit` tables after `start_after` in the table index. Tables in the middle of an escrow hand
+     * are left alone, their chips only come back at the end of the hand.
+     * A closed table leaves its place in the index to the last one, which is looked at next. Until the response
+     * says `finished`, the sweep goes on from its `start_after`: the last table kept, from the start without one.
+     */
+    pub fn handle_prune_tables(
+        deps: DepsMut,
+        older_than: Timestamp,
+        start_after: Option<u32>,
+        limit: Option<u32>,
+    ) -> Result<Response, ContractError> {
+        let mut left = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+        let mut last_kept = start_after;
+        let mut pruned = vec![];
+        while left > 0 {
+            let next = table_ids(deps.storage, last_kept, 1)?.ok_or(ContractError::TableNotFound {
+                table_id: last_kept.unwrap_or_default(),
+            })?;
+            let Some(&table_id) = next.first() else {
+                break;
+            };
+            let idle = load_table(deps.storage, table_id)
+                .is_some_and(|table| table.last_activity() < older_than && !holds_escrow(&table));
+            if idle {
+                close_table(deps.storage, table_id, vec![])?;
+                pruned.push(table_id);
+            } else {
+                last_kept = Some(table_id);
+            }
+            left -= 1;
+        }
+        let pruned = serde_json_wasm::to_string(&pruned).map_err(|e| ContractError::SerializationFailed {
+            error: e.to_string(),
+        })?;
+        let finished = left > 0;
+        let mut res = Response::new()
+            .add_attribute_plaintext("pruned_tables", pruned)
+            .add_attribute_plaintext("finished", finished.to_string());
+        if let Some(last_kept) = last_kept.filter(|_| !finished) {
+            res = res.add_attribute_plaintext("start_after", last_kept.to_string());
+        }
+        Ok(res)
+    }
+
+    fn holds_escrow(table: &PokerTable) -> bool {
+        table.game_state != GameState::Closed && table.betting.as_ref().is_some_and(|betting| betting.escrow.is_some())
+    }
+
+    fn close_table(
+        storage: &mut dyn Storage,
+        table_id: u32,
+        showdown_player_ids: Vec<Uuid>,
+    ) -> Result<LastHandLogResponse, ContractError> {
+        let hand_log = create_previous_hand_log(storage, table_id, showdown_player_ids)?
+            .ok_or(ContractError::TableNotFound { table_id })?;
+        archive_hand(storage, table_id, &hand_log)?;
+        delete_table(storage, table_id)?;
+        Ok(hand_log)
+    }
+
+    pub fn handle_set_rake(deps: DepsMut, rake: Option<RakeConfig>) -> Result<Response, ContractError> {
+        let mut config = CONFIG_KEY.load(deps.storage)?;
+        config.rake = rake.map(helpers::validate_rake).transpose()?;
+        CONFIG_KEY.save(deps.storage, &config)?;
+        Ok(Response::new())
+    }
+
+    /*
+     * Sends the whole treasury, in every currency that collected rake, to the recipient.
+     * The off-chain rake is cleared and reported in the "off_chain_rake" attribute.
+     */
+    pub fn handle_withdraw_rake(
+        deps: DepsMut,
+        owner: Addr,
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let recipient = match recipient {
+            Some(recipient) => deps.api.addr_validate(&recipient)?,
+            None => owner,
+        };
+        let token = CONFIG_KEY.load(deps.storage)?.accepted_token;
+
+        let mut res = Response::new();
+        for currency in [Currency::Token, Currency::Native] {
+            let treasury = TREASURY.get(deps.storage, &currency).unwrap_or_default();
+            if !treasury.is_zero() {
+                TREASURY.remove(deps.storage, &currency)?;
+                let transfer = payout_msg(deps.storage, &currency, token.as_ref(), recipient.to_string(), treasury)?;
+                res = res.add_message(transfer);
+            }
+        }
+        let off_chain = OFF_CHAIN_RAKE.may_load(deps.storage)?.unwrap_or_default();
+        if !off_chain.is_zero() {
+            OFF_CHAIN_RAKE.remove(deps.storage);
+            res = res.add_attribute_plaintext("off_chain_rake", off_chain.to_string());
+        }
+        if res.messages.is_empty() && off_chain.is_zero() {
+            return Err(ContractError::EmptyTreasury {});
+        }
+        Ok(res)
+    }
+
+    pub fn handle_add_operator(deps: DepsMut, address: String) -> Result<Response, ContractError> {
+        let operator = deps.api.addr_validate(&address)?;
+        let mut config = CONFIG_KEY.load(deps.storage)?;
+        if config.operators.contains(&operator) {
+            return Err(ContractError::OperatorExists { operator: operator.to_string() });
+        }
+        config.operators.push(operator);
+        CONFIG_KEY.save(deps.storage, &config)?;
+        Ok(Response::new())
+    }
+
+    pub fn handle_remove_operator(deps: DepsMut, address: String) -> Result<Response, ContractError> {
+        let operator = deps.api.addr_validate(&address)?;
+        let mut config = CONFIG_KEY.load(deps.storage)?;
+        let index = config
+            .operators
+            .iter()
+            .position(|existing| *existing == operator)
+            .ok_or_else(|| ContractError::OperatorNotFound { operator: operator.to_string() })?;
+        config.operators.remove(index);
+        CONFIG_KEY.save(deps.storage, &config)?;
+        Ok(Response::new())
+    }
+
+    /*
+     * A later proposal replaces the pending one, the owner stays in charge until the new owner accepts.
+     */
+    pub fn handle_transfer_ownership(deps: DepsMut, new_owner: String) -> Result<Response, ContractError> {
+        let mut config = CONFIG_KEY.load(deps.storage)?;
+        config.pending_owner = Some(deps.api.addr_validate(&new_owner)?);
+        CONFIG_KEY.save(deps.storage, &config)?;
+        Ok(Response::new())
+    }
+
+    pub fn handle_accept_ownership(deps: DepsMut) -> Result<Response, ContractError> {
+        let mut config = CONFIG_KEY.load(deps.storage)?;
+        if let Some(new_owner) = config.pending_owner.take() {
+            config.owner = new_owner;
+        }
+        CONFIG_KEY.save(deps.storage, &config)?;
+        Ok(Response::new())
+    }
+
+    pub fn handle_set_paused(
+        deps: DepsMut,
+        paused: bool,
+        reason: String,
+        all_execution: bool,
+    ) -> Result<Response, ContractError> {
+        let mut config = CONFIG_KEY.load(deps.storage)?;
+        config.pause = paused.then_some(Pause { reason, all_execution });
+        CONFIG_KEY.save(deps.storage, &config)?;
+        Ok(Response::new().add_attribute_plaintext("paused", paused.to_string()))
+    }
+
+    fn take_balance(
+        storage: &mut dyn Storage,
+        table_id: u32,
+        currency: &Currency,
+        player: &Addr,
+    ) -> Result<Uint128, ContractError> {
+        let balance = load_balance(storage, table_id, currency, player.as_str());
+        if balance.is_zero() {
+            return Err(ContractError::InsufficientBalance {
+                table_id,
+                player: player.to_string(),
+                balance,
+            });
+        }
+        save_balance(storage, table_id, currency, player.as_str(), Uint128::zero())?;
+        Ok(balance)
+    }
+
+    fn handle_all_in_showdown(
+        community_cards: &CommunityCards,
+        game_state: GameState,
+    ) -> Option<Vec<Card>> {
+        match game_state {
+            GameState::PreFlop => {
+                let mut cards = community_cards.flop.cards.clone();
+                cards.push(community_cards.turn.card.clone());
+                cards.push(community_cards.river.card.clone());
+                Some(cards)
+            }
+            GameState::Flop => Some(vec![
+                community_cards.turn.card.clone(),
+                community_cards.river.card.clone(),
+            ]),
+            GameState::Turn => Some(vec![community_cards.river.card.clone()]),
+            _ => None,
+        }
+    }
+}
+
+
+#[entry_point]
+pub fn instantiate(deps: DepsMut, env: Env, info: MessageInfo, msg: InstantiateMsg,) -> Result<Response, ContractError> {
+    let accepted_token = msg
+        .accepted_token
+        .map(|token| execute_handlers::validate_token(deps.as_ref(), token))
+        .transpose()?;
+    let config = Config {
+        owner: info.sender,
+        pending_owner: None,
+        operators: vec![],
+        pause: None,
+        contract_address: env.contract.address.clone(),
+        accepted_token,
+        rake: msg.rake.map(helpers::validate_rake).transpose()?,
+    };
+
+    let counter = init_counter(&env)?;
+
+    CONFIG_KEY.save(deps.storage, &config)?;
+    COUNTER_KEY.save(deps.storage, &counter)?;
+
+    let mut res = Response::default();
+    if let Some(token) = &config.accepted_token {
+        res = res.add_message(snip20::register_receive_msg(token, env.contract.code_hash)?);
+    }
+    Ok(res)
+}
+
+fn init_counter(env: &Env) -> StdResult<u128> {
+    let seed = env
+        .block
         .random
         .as_ref()
         .ok_or(StdError::generic_err("No random seed available"))?;
@@ -594,9 +1993,45 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     let config = CONFIG_KEY.load(deps.storage)?;
-    if info.sender != config.owner {
+    let authorized = match &msg {
+        // players sign their own shares, bets, buy-ins and withdrawals, Receive comes from the accepted token
+        ExecuteMsg::SubmitShare { .. }
+            | ExecuteMsg::Fold { .. }
+            | ExecuteMsg::Check { .. }
+            | ExecuteMsg::Call { .. }
+            | ExecuteMsg::Bet { .. }
+            | ExecuteMsg::Raise { .. }
+            | ExecuteMsg::AllIn { .. }
+            | ExecuteMsg::Receive { .. }
+            | ExecuteMsg::Withdraw { .. }
+            | ExecuteMsg::JoinTable { .. }
+            | ExecuteMsg::LeaveTable { .. } => true,
+        // the operators create the tables and reveal the cards of the tables they run
+        ExecuteMsg::StartGame { table_id, .. }
+            | ExecuteMsg::CommunityCards { table_id, .. }
+            | ExecuteMsg::Showdown { table_id, .. }
+            | ExecuteMsg::RunItMultiple { table_id, .. }
+            | ExecuteMsg::VoidHand { table_id, .. }
+            | ExecuteMsg::CloseTable { table_id, .. } => {
+            state_utils::runs_table(deps.storage, &config, &info.sender, *table_id)
+        }
+        ExecuteMsg::AcceptOwnership {} => config.pending_owner.as_ref() == Some(&info.sender),
+        // the configuration stays with the owner
+        _ => info.sender == config.owner,
+    };
+    if !authorized {
         return Err(ContractError::Unauthorized {});
     }
+    if let Some(pause) = &config.pause {
+        let paused = match msg {
+            ExecuteMsg::SetPaused { .. } => false,
+            ExecuteMsg::StartGame { .. } => true,
+            _ => pause.all_execution,
+        };
+        if paused {
+            return Err(ContractError::Paused { reason: pause.reason.clone() });
+        }
+    }
 
     match msg {
         ExecuteMsg::StartGame {
@@ -604,13 +2039,26 @@ pub fn execute(
             hand_ref,
             players,
             prev_hand_showdown_players,
+            threshold,
+            game_variant,
+            deck_type,
+            burn_cards,
+            betting,
+            force,
         } => execute_handlers::handle_start_game(
             deps,
             env,
+            info.sender,
             table_id,
             hand_ref,
             players,
             prev_hand_showdown_players,
+            threshold,
+            game_variant,
+            deck_type,
+            burn_cards,
+            betting,
+            force,
         ),
         ExecuteMsg::CommunityCards {
             table_id,
@@ -620,22 +2068,90 @@ pub fn execute(
             table_id,
             game_state,
             showdown_player_ids,
-        } => execute_handlers::handle_showdown(deps, env, table_id, game_state, showdown_player_ids),
-    }
-}
-
-#[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::WithPermit { permit, query } => {
-            query_handlers::handle_permit_query(deps, permit, query)
-        }
-        QueryMsg::CommunityCards {
+            contributions,
+            pot,
+        } => execute_handlers::handle_showdown(deps, env, table_id, game_state, showdown_player_ids, contributions, pot),
+        ExecuteMsg::RunItMultiple {
             table_id,
             game_state,
-            secret_key,
+            runs,
+        } => execute_handlers::handle_run_it_multiple(deps, table_id, game_state, runs),
+        ExecuteMsg::SubmitShare {
+            table_id,
+            phase,
+            share,
+        } => execute_handlers::handle_submit_share(deps, env, info.sender, table_id, phase, share),
+        ExecuteMsg::Fold { table_id } => {
+            execute_handlers::handle_player_action(deps, env, info.sender, table_id, PlayerAction::Fold, Uint128::zero())
+        }
+        ExecuteMsg::Check { table_id } => {
+            execute_handlers::handle_player_action(deps, env, info.sender, table_id, PlayerAction::Check, Uint128::zero())
+        }
+        ExecuteMsg::Call { table_id } => {
+            execute_handlers::handle_player_action(deps, env, info.sender, table_id, PlayerAction::Call, Uint128::zero())
+        }
+        ExecuteMsg::Bet { table_id, amount } => {
+            execute_handlers::handle_player_action(deps, env, info.sender, table_id, PlayerAction::Bet, amount)
+        }
+        ExecuteMsg::Raise { table_id, amount } => {
+            execute_handlers::handle_player_action(deps, env, info.sender, table_id, PlayerAction::Raise, amount)
+        }
+        ExecuteMsg::AllIn { table_id } => {
+            execute_handlers::handle_player_action(deps, env, info.sender, table_id, PlayerAction::AllIn, Uint128::zero())
+        }
+        ExecuteMsg::SetAcceptedToken { token } => execute_handlers::handle_set_accepted_token(deps, env, token),
+        ExecuteMsg::Receive {
+            from,
+            amount,
+            msg,
+            ..
+        } => execute_handlers::handle_receive(deps, info.sender, from, amount, msg),
+        ExecuteMsg::Withdraw { table_id } => execute_handlers::handle_withdraw(deps, info.sender, table_id),
+        ExecuteMsg::JoinTable { table_id } => {
+            execute_handlers::handle_join_table(deps, info.sender, table_id, info.funds)
+        }
+        ExecuteMsg::LeaveTable { table_id } => execute_handlers::handle_leave_table(deps, info.sender, table_id),
+        ExecuteMsg::SetRake { rake } => execute_handlers::handle_set_rake(deps, rake),
+        ExecuteMsg::WithdrawRake { recipient } => execute_handlers::handle_withdraw_rake(deps, info.sender, recipient),
+        ExecuteMsg::AddOperator { address } => execute_handlers::handle_add_operator(deps, address),
+        ExecuteMsg::RemoveOperator { address } => execute_handlers::handle_remove_operator(deps, address),
+        ExecuteMsg::TransferOwnership { new_owner } => execute_handlers::handle_transfer_ownership(deps, new_owner),
+        ExecuteMsg::AcceptOwnership {} => execute_handlers::handle_accept_ownership(deps),
+        ExecuteMsg::VoidHand { table_id, reason } => execute_handlers::handle_void_hand(deps, table_id, reason),
+        ExecuteMsg::CloseTable {
+            table_id,
+            showdown_player_ids,
+        } => execute_handlers::handle_close_table(deps, table_id, showdown_player_ids),
+        ExecuteMsg::PruneTables { older_than, start_after, limit } => {
+            execute_handlers::handle_prune_tables(deps, older_than, start_after, limit)
+        }
+        ExecuteMsg::SetPaused {
+            paused,
+            reason,
+            all_execution,
+        } => execute_handlers::handle_set_paused(deps, paused, reason, all_execution),
+    }
+}
+
+#[entry_point]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::WithPermit { permit, query } => {
+            query_handlers::handle_permit_query(deps, permit, query)
+        }
+        QueryMsg::Config {} => to_binary(&query_handlers::query_config(deps)?),
+        QueryMsg::Tables { start_after, limit } => to_binary(&query_handlers::query_tables(deps, start_after, limit)?),
+        QueryMsg::HandHistory {
+            table_id,
+            start_after,
+            limit,
+        } => to_binary(&query_handlers::query_hand_history(deps, table_id, start_after, limit)?),
+        QueryMsg::CommunityCards {
+            table_id,
+            game_state,
+            shares,
         } => to_binary(&query_handlers::query_community_cards(
-            deps, table_id, game_state, secret_key,
+            deps, table_id, game_state, shares,
         )?),
         QueryMsg::Showdown {
             table_id,
@@ -658,146 +2174,2224 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
 mod complete_tests {
     use crate::contract::query_handlers::query_player_private_data;
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use crate::msg::PlayerDataResponse;
     use cosmwasm_std::{coins, from_binary};
+    use sha2::{Digest, Sha256};
     use super::*;
 
     #[test]
-    fn test_instantiate() {
+    fn test_instantiate() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
+        let info = mock_info("creator", &coins(1000, "earth"));
+
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+    }
+
+    #[test]
+    fn test_start_game() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let players = vec![
+            StartGamePlayer {
+                username: "player1".to_string(),
+                player_id: Uuid::parse_str("2928c53b-5d14-4a7c-b56e-83ef56a0644e").unwrap(),
+                public_key: "key1".to_string(),
+                stack: Uint128::zero(),
+            },
+            StartGamePlayer {
+                username: "player2".to_string(),
+                player_id: Uuid::parse_str("8f204fcc-54a5-4473-8ac3-4845bff291ab").unwrap(),
+                public_key: "key2".to_string(),
+                stack: Uint128::zero(),
+            },
+        ];
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            NewHand {
+                players,
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        
+        let attrs = &res.attributes;
+        let response_attr = attrs.iter().find(|attr| attr.key == "response").unwrap();
+        assert!(response_attr.value.contains("\"players\":[\"player1\",\"player2\"]"));
+        assert!(response_attr.value.contains("\"table_id\":1"));
+        assert!(response_attr.value.contains("\"hand_ref\":1"));
+
+        let player_info1 = query_player_private_data(deps.as_ref(), 1, "key1".to_string()).unwrap();
+        let player_info2 = query_player_private_data(deps.as_ref(), 1, "key2".to_string()).unwrap();
+        
+        
+        assert_eq!(player_info1.table_id, 1);
+        assert_eq!(player_info1.hand_ref, 1);
+        assert_eq!(player_info1.hand.len(), 2);
+        assert!(player_info1.flop_secret_share.parse::<u64>().is_ok());
+        
+        assert_eq!(player_info2.table_id, 1);
+        assert_eq!(player_info2.hand_ref, 1);
+        assert_eq!(player_info2.hand.len(), 2);
+        assert!(player_info2.flop_secret_share.parse::<u64>().is_ok());
+        
+        let flop_shares = player_shares(&[&player_info1, &player_info2], GameState::Flop);
+        let turn_shares = player_shares(&[&player_info1, &player_info2], GameState::Turn);
+        let river_shares = player_shares(&[&player_info1, &player_info2], GameState::River);
+
+        
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::CommunityCards {
+                table_id: 1,
+                game_state: GameState::Flop,
+                shares: flop_shares.clone(),
+            },
+        );
+        let flop_response: CommunityCardsResponse = from_binary(res.as_ref().unwrap()).unwrap();
+        assert_eq!(flop_response.table_id, 1);
+        assert_eq!(flop_response.hand_ref, 1);
+        assert_eq!(flop_response.game_state, GameState::Flop);
+        assert_eq!(flop_response.community_cards.len(), 3);
+
+        
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::CommunityCards {
+                table_id: 1,
+                game_state: GameState::Turn,
+                shares: turn_shares,
+            },
+        );
+        let turn_response: CommunityCardsResponse = from_binary(res.as_ref().unwrap()).unwrap();
+        assert_eq!(turn_response.table_id, 1);
+        assert_eq!(turn_response.hand_ref, 1);
+        assert_eq!(turn_response.game_state, GameState::Turn);
+        assert_eq!(turn_response.community_cards.len(), 1);
+
+        
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::CommunityCards {
+                table_id: 1,
+                game_state: GameState::River,
+                shares: river_shares,
+            },
+        );
+        let river_response: CommunityCardsResponse = from_binary(res.as_ref().unwrap()).unwrap();
+        assert_eq!(river_response.table_id, 1);
+        assert_eq!(river_response.hand_ref, 1);
+        assert_eq!(river_response.game_state, GameState::River);
+        assert_eq!(river_response.community_cards.len(), 1);
+
+        
+        let mut tampered_shares = flop_shares.clone();
+        tampered_shares[0].share += 1;
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::CommunityCards {
+                table_id: 1,
+                game_state: GameState::Flop,
+                shares: tampered_shares,
+            },
+        );
+        assert!(res.is_err());
+
+        // an additive table needs the share of every player
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::CommunityCards {
+                table_id: 1,
+                game_state: GameState::Flop,
+                shares: flop_shares[..1].to_vec(),
+            },
+        );
+        assert!(res.is_err());
+
+        let flop_secret = addition_shares(flop_shares.iter().map(|s| s.share).collect());
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Showdown {
+                table_id: 1,
+                flop_secret: Some(flop_secret),
+                turn_secret: None,
+                river_secret: None,
+                players_secrets: vec![],
+            },
+        );
+        let showdown_response: ShowdownResponse = from_binary(res.as_ref().unwrap()).unwrap();
+        assert_eq!(showdown_response.community_cards, Some(flop_response.community_cards));
+    }
+
+    #[test]
+    fn test_threshold_community_cards() {
+        let mut deps = mock_dependencies();
+        
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        
+        let players = vec![
+            StartGamePlayer {
+                username: "player1".to_string(),
+                player_id: Uuid::parse_str("2928c53b-5d14-4a7c-b56e-83ef56a0644e").unwrap(),
+                public_key: "key1".to_string(),
+                stack: Uint128::zero(),
+            },
+            StartGamePlayer {
+                username: "player2".to_string(),
+                player_id: Uuid::parse_str("8f204fcc-54a5-4473-8ac3-4845bff291ab").unwrap(),
+                public_key: "key2".to_string(),
+                stack: Uint128::zero(),
+            },
+            StartGamePlayer {
+                username: "player3".to_string(),
+                player_id: Uuid::parse_str("e6799ecf-f202-418a-a535-0b42509f69f7").unwrap(),
+                public_key: "key3".to_string(),
+                stack: Uint128::zero(),
+            },
+        ];
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                players: players.clone(),
+                threshold: Some(4),
+                ..NewHand::default()
+            }
+            .into(),
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            ContractError::InvalidThreshold { threshold: 4, players: 3 }
+        );
+        
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                players,
+                threshold: Some(2),
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        
+        let player_info1 = query_player_private_data(deps.as_ref(), 1, "key1".to_string()).unwrap();
+        let player_info3 = query_player_private_data(deps.as_ref(), 1, "key3".to_string()).unwrap();
+        assert_eq!(player_info1.share_index, 1);
+        assert_eq!(player_info3.share_index, 3);
+        
+        // player2 disconnected, the two remaining players can still open every street
+        for game_state in [GameState::Flop, GameState::Turn, GameState::River] {
+            let res = query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::CommunityCards {
+                    table_id: 1,
+                    game_state: game_state.clone(),
+                    shares: player_shares(&[&player_info1, &player_info3], game_state),
+                },
+            );
+            assert!(res.is_ok());
+        }
+        
+        // a single share is below the threshold
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::CommunityCards {
+                table_id: 1,
+                game_state: GameState::Flop,
+                shares: player_shares(&[&player_info3], GameState::Flop),
+            },
+        );
+        assert!(res.is_err());
+        
+        // the same share twice does not count as two players
+        let mut duplicated = player_shares(&[&player_info3], GameState::Flop);
+        duplicated.push(duplicated[0].clone());
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::CommunityCards {
+                table_id: 1,
+                game_state: GameState::Flop,
+                shares: duplicated,
+            },
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_submit_share() {
+        let mut deps = mock_dependencies();
+        
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        
+        let players = vec![
+            StartGamePlayer {
+                username: "player1".to_string(),
+                player_id: Uuid::parse_str("2928c53b-5d14-4a7c-b56e-83ef56a0644e").unwrap(),
+                public_key: "key1".to_string(),
+                stack: Uint128::zero(),
+            },
+            StartGamePlayer {
+                username: "player2".to_string(),
+                player_id: Uuid::parse_str("8f204fcc-54a5-4473-8ac3-4845bff291ab").unwrap(),
+                public_key: "key2".to_string(),
+                stack: Uint128::zero(),
+            },
+            StartGamePlayer {
+                username: "player3".to_string(),
+                player_id: Uuid::parse_str("e6799ecf-f202-418a-a535-0b42509f69f7").unwrap(),
+                public_key: "key3".to_string(),
+                stack: Uint128::zero(),
+            },
+        ];
+        
+        // table 1 is 2-of-3, table 2 needs every share
+        for (table_id, threshold) in [(1, Some(2)), (2, None)] {
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                NewHand {
+                    table_id,
+                    players: players.clone(),
+                    threshold,
+                    ..NewHand::default()
+                }
+                .into(),
+            )
+            .unwrap();
+        }
+        
+        let submit = |deps: &mut cosmwasm_std::OwnedDeps<_, _, _>, sender: &str, table_id: u32, phase: GameState| {
+            let player = query_player_private_data(deps.as_ref(), table_id, sender.to_string()).unwrap();
+            let share = player_shares(&[&player], phase.clone())[0].share;
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::SubmitShare { table_id, phase, share },
+            )
+        };
+        
+        let res = submit(&mut deps, "key1", 1, GameState::Flop).unwrap();
+        assert!(res.events.is_empty());
+        assert!(!res.attributes.iter().any(|attr| attr.key == "response"));
+        
+        assert_eq!(
+            submit(&mut deps, "key1", 1, GameState::Flop).unwrap_err(),
+            ContractError::DuplicateSecretShare {
+                table_id: 1,
+                player: "key1".to_string(),
+                game_state: GameState::Flop,
+            }
+        );
+        assert!(matches!(
+            submit(&mut deps, "key2", 1, GameState::Turn).unwrap_err(),
+            ContractError::GameStateError { expected: Some(GameState::Flop), .. }
+        ));
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("key2", &[]),
+            ExecuteMsg::SubmitShare { table_id: 1, phase: GameState::Flop, share: 42 },
+        );
+        assert!(matches!(res.unwrap_err(), ContractError::InvalidSecretShare { .. }));
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("stranger", &[]),
+            ExecuteMsg::SubmitShare { table_id: 1, phase: GameState::Flop, share: 42 },
+        );
+        assert!(matches!(res.unwrap_err(), ContractError::PlayerNotFound { .. }));
+        
+        // the second share reaches the threshold and unlocks the flop
+        let res = submit(&mut deps, "key3", 1, GameState::Flop).unwrap();
+        assert_eq!(res.events.len(), 1);
+        assert_eq!(res.events[0].ty, "community_cards_unlocked");
+        assert!(res.events[0].attributes.iter().any(|attr| attr.key == "game_state" && attr.value == "flop"));
+        let response_attr = res.attributes.iter().find(|attr| attr.key == "response").unwrap();
+        match serde_json_wasm::from_str(&response_attr.value).unwrap() {
+            ResponsePayload::CommunityCards(cards_response) => {
+                assert_eq!(cards_response.game_state, GameState::Flop);
+                assert_eq!(cards_response.community_cards.len(), 3);
+            }
+            _ => panic!("Expected CommunityCards response"),
+        }
+        assert_eq!(load_table(deps.as_ref().storage, 1).unwrap().game_state, GameState::Flop);
+        
+        // the additive table stays locked until the last player submits
+        assert!(submit(&mut deps, "key1", 2, GameState::Flop).unwrap().events.is_empty());
+        assert!(submit(&mut deps, "key2", 2, GameState::Flop).unwrap().events.is_empty());
+        assert_eq!(load_table(deps.as_ref().storage, 2).unwrap().game_state, GameState::PreFlop);
+        assert_eq!(submit(&mut deps, "key3", 2, GameState::Flop).unwrap().events.len(), 1);
+        assert_eq!(load_table(deps.as_ref().storage, 2).unwrap().game_state, GameState::Flop);
+    }
+
+    fn test_players(count: u128) -> Vec<StartGamePlayer> {
+        (1..=count)
+            .map(|i| StartGamePlayer {
+                username: format!("player{}", i),
+                player_id: Uuid::from_u128(i),
+                public_key: format!("key{}", i),
+                stack: Uint128::zero(),
+            })
+            .collect()
+    }
+
+    /* *
+     * The fields of a StartGame message, so tests only spell out the ones they care about.
+     * The default is a fresh two player HoldEm hand at table 1.
+     * */
+    struct NewHand {
+        table_id: u32,
+        hand_ref: u32,
+        players: Vec<StartGamePlayer>,
+        prev_hand_showdown_players: Vec<Uuid>,
+        threshold: Option<u32>,
+        game_variant: GameVariant,
+        deck_type: DeckType,
+        burn_cards: bool,
+        betting: Option<BettingConfig>,
+        force: bool,
+    }
+
+    impl Default for NewHand {
+        fn default() -> Self {
+            NewHand {
+                table_id: 1,
+                hand_ref: 1,
+                players: test_players(2),
+                prev_hand_showdown_players: vec![],
+                threshold: None,
+                game_variant: GameVariant::HoldEm,
+                deck_type: DeckType::Standard,
+                burn_cards: false,
+                betting: None,
+                force: false,
+            }
+        }
+    }
+
+    impl From<NewHand> for ExecuteMsg {
+        fn from(hand: NewHand) -> Self {
+            ExecuteMsg::StartGame {
+                table_id: hand.table_id,
+                hand_ref: hand.hand_ref,
+                players: hand.players,
+                prev_hand_showdown_players: hand.prev_hand_showdown_players,
+                threshold: hand.threshold,
+                game_variant: hand.game_variant,
+                deck_type: hand.deck_type,
+                burn_cards: hand.burn_cards,
+                betting: hand.betting,
+                force: hand.force,
+            }
+        }
+    }
+
+    fn start_game_msg(table_id: u32, hand_ref: u32) -> ExecuteMsg {
+        NewHand {
+            table_id,
+            hand_ref,
+            force: true, // the hands are not played out
+            ..NewHand::default()
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_omaha_dealing() {
+        let mut deps = mock_dependencies();
+        
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                players: test_players(3),
+                game_variant: GameVariant::Omaha4,
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        let response_attr = res.attributes.iter().find(|attr| attr.key == "response").unwrap();
+        assert!(response_attr.value.contains("\"game_variant\":\"omaha4\""));
+        
+        let mut dealt = HashSet::new();
+        for key in ["key1", "key2", "key3"] {
+            let player_info = query_player_private_data(deps.as_ref(), 1, key.to_string()).unwrap();
+            assert_eq!(player_info.hand.len(), 4);
+            dealt.extend(player_info.hand.iter().map(|card| card.to_bytes()));
+        }
+        assert_eq!(dealt.len(), 12);
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Showdown {
+                table_id: 1,
+                game_state: GameState::PreFlop,
+                showdown_player_ids: vec![Uuid::from_u128(1), Uuid::from_u128(3)],
+                contributions: vec![],
+                pot: None,
+            },
+        )
+        .unwrap();
+        let response_attr = res.attributes.iter().find(|attr| attr.key == "response").unwrap();
+        match serde_json_wasm::from_str(&response_attr.value).unwrap() {
+            ResponsePayload::Showdown(showdown) => {
+                assert!(showdown.players_cards.iter().all(|(_, hand)| hand.len() == 4));
+                let board = showdown.community_cards.unwrap();
+                assert_eq!(board.len(), 5);
+                assert!(board.iter().all(|card| !dealt.contains(&card.to_bytes())));
+            }
+            _ => panic!("Expected Showdown response"),
+        }
+        
+        // 52 cards minus the board leave room for 9 five-card hands and 23 two-card hands
+        for (game_variant, max_players) in [(GameVariant::Omaha5, 9), (GameVariant::Omaha4, 11), (GameVariant::HoldEm, 23)] {
+            for (count, ok) in [(max_players, true), (max_players + 1, false)] {
+                let res = execute(
+                    deps.as_mut(),
+                    mock_env(),
+                    info.clone(),
+                    NewHand {
+                        table_id: 2,
+                        hand_ref: count,
+                        players: test_players(count as u128),
+                        game_variant: game_variant.clone(),
+                        force: true,
+                        ..NewHand::default()
+                    }
+                    .into(),
+                );
+                if ok {
+                    assert!(res.is_ok());
+                } else {
+                    assert_eq!(res.unwrap_err(), ContractError::InvalidPlayerCount { count: count as usize });
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_short_deck() {
+        let mut deps = mock_dependencies();
+        
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                players: test_players(16),
+                deck_type: DeckType::ShortDeck,
+                ..NewHand::default()
+            }
+            .into(),
+        );
+        assert_eq!(res.unwrap_err(), ContractError::InvalidPlayerCount { count: 16 });
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                players: test_players(15),
+                deck_type: DeckType::ShortDeck,
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        let response_attr = res.attributes.iter().find(|attr| attr.key == "response").unwrap();
+        assert!(response_attr.value.contains("\"deck_type\":\"short_deck\""));
+        
+        let player_info = query_player_private_data(deps.as_ref(), 1, "key1".to_string()).unwrap();
+        assert!(player_info.hand.iter().all(|card| card.rank() == 1 || card.rank() >= 6));
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                hand_ref: 2,
+                prev_hand_showdown_players: vec![Uuid::from_u128(1)],
+                force: true, // the first hand was never played out
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        let log_attr = res.attributes.iter().find(|attr| attr.key == "previous_hand_log").unwrap();
+        match serde_json_wasm::from_str(&log_attr.value).unwrap() {
+            ResponsePayload::LastHand(log) => {
+                assert_eq!(log.deck_type, DeckType::ShortDeck);
+                let deck = Deck::from_bytes(log.deck.as_slice());
+                assert_eq!(deck.cards.len(), 36);
+                assert_eq!(log.showdown_players[0].hand, player_info.hand.iter().map(|card| card.to_string()).collect::<Vec<_>>());
+                assert_eq!(log.community_cards.len(), 5);
+            }
+            _ => panic!("Expected LastHand response"),
+        }
+    }
+
+    #[test]
+    fn test_burn_cards() {
+        let mut deps = mock_dependencies();
+        
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        
+        // 52 - 5 board cards - 3 burn cards leave room for 8 five-card hands
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                players: test_players(9),
+                game_variant: GameVariant::Omaha5,
+                burn_cards: true,
+                ..NewHand::default()
+            }
+            .into(),
+        );
+        assert_eq!(res.unwrap_err(), ContractError::InvalidPlayerCount { count: 9 });
+        
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                burn_cards: true,
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        
+        let mut board = Vec::new();
+        for game_state in [GameState::Flop, GameState::Turn, GameState::River] {
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::CommunityCards { table_id: 1, game_state },
+            )
+            .unwrap();
+            let response_attr = res.attributes.iter().find(|attr| attr.key == "response").unwrap();
+            match serde_json_wasm::from_str(&response_attr.value).unwrap() {
+                ResponsePayload::CommunityCards(cards_response) => board.extend(cards_response.community_cards),
+                _ => panic!("Expected CommunityCards response"),
+            }
+        }
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                hand_ref: 2,
+                force: true, // the first hand was never played out
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        let log_attr = res.attributes.iter().find(|attr| attr.key == "previous_hand_log").unwrap();
+        let log = match serde_json_wasm::from_str(&log_attr.value).unwrap() {
+            ResponsePayload::LastHand(log) => log,
+            _ => panic!("Expected LastHand response"),
+        };
+        
+        // hole cards are 51..=48, then burn 47, flop 46..=44, burn 43, turn 42, burn 41, river 40
+        let deck = Deck::from_bytes(log.deck.as_slice());
+        let expected_burned: Vec<String> = [47, 43, 41].iter().map(|&i| deck.cards[i].to_string()).collect();
+        let expected_board: Vec<Card> = [46, 45, 44, 42, 40].iter().map(|&i| deck.cards[i].clone()).collect();
+        assert_eq!(log.burned_cards, expected_burned);
+        assert_eq!(board, expected_board);
+        assert_eq!(log.community_cards, board.iter().map(|card| card.to_string()).collect::<Vec<_>>());
+        
+        // the hand without burning logs no burned cards
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                hand_ref: 3,
+                force: true,
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        let log_attr = res.attributes.iter().find(|attr| attr.key == "previous_hand_log").unwrap();
+        assert!(log_attr.value.contains("\"burned_cards\":[]"));
+    }
+
+    #[test]
+    fn test_run_it_multiple() {
+        let mut deps = mock_dependencies();
+        
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        
+        for table_id in [1, 2] {
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                NewHand {
+                    table_id,
+                    ..NewHand::default()
+                }
+                .into(),
+            )
+            .unwrap();
+        }
+        
+        // 52 - 4 hole cards - 5 board cards leave 43 cards, enough for 8 extra preflop boards
+        for (runs, ok) in [(1, false), (10, false), (9, true)] {
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::RunItMultiple { table_id: 2, game_state: GameState::PreFlop, runs },
+            );
+            if ok {
+                assert!(res.is_ok());
+            } else {
+                assert_eq!(res.unwrap_err(), ContractError::InvalidRunCount { table_id: 2, runs });
+            }
+        }
+        
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::CommunityCards { table_id: 1, game_state: GameState::Flop },
+        )
+        .unwrap();
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::RunItMultiple { table_id: 1, game_state: GameState::PreFlop, runs: 2 },
+        );
+        assert!(matches!(res.unwrap_err(), ContractError::GameStateError { .. }));
+        
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::RunItMultiple { table_id: 1, game_state: GameState::Flop, runs: 2 },
+        )
+        .unwrap();
+        
+        // the boards are dealt, nothing can be revealed on its own any more
+        for game_state in [GameState::Turn, GameState::Showdown] {
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::CommunityCards { table_id: 1, game_state: game_state.clone() },
+            );
+            assert!(matches!(res.unwrap_err(), ContractError::GameStateError { expected: None, .. }));
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("key1", &[]),
+                ExecuteMsg::SubmitShare { table_id: 1, phase: game_state, share: 0 },
+            );
+            assert!(matches!(res.unwrap_err(), ContractError::GameStateError { expected: None, .. }));
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::RunItMultiple { table_id: 1, game_state: GameState::Flop, runs: 2 },
+        );
+        assert!(matches!(res.unwrap_err(), ContractError::GameStateError { .. }));
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Showdown {
+                table_id: 1,
+                game_state: GameState::Flop,
+                showdown_player_ids: vec![Uuid::from_u128(1), Uuid::from_u128(2)],
+                contributions: vec![],
+                pot: None,
+            },
+        )
+        .unwrap();
+        let response_attr = res.attributes.iter().find(|attr| attr.key == "response").unwrap();
+        let boards = match serde_json_wasm::from_str(&response_attr.value).unwrap() {
+            ResponsePayload::Showdown(showdown) => {
+                assert_eq!(showdown.boards.len(), 2);
+                assert_eq!(Some(showdown.boards[0].clone()), showdown.community_cards);
+                assert_eq!(showdown.results.len(), 2);
+                assert_eq!(showdown.results[1].board[3..], showdown.boards[1][..]);
+                showdown.boards
+            }
+            _ => panic!("Expected Showdown response"),
+        };
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                hand_ref: 2,
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        let log_attr = res.attributes.iter().find(|attr| attr.key == "previous_hand_log").unwrap();
+        let log = match serde_json_wasm::from_str(&log_attr.value).unwrap() {
+            ResponsePayload::LastHand(log) => log,
+            _ => panic!("Expected LastHand response"),
+        };
+        // the second turn and river come right after the first river (deck index 43)
+        let deck = Deck::from_bytes(log.deck.as_slice());
+        assert_eq!(boards[1], vec![deck.cards[42].clone(), deck.cards[41].clone()]);
+        assert_eq!(log.extra_runs, vec![boards[1].iter().map(|card| card.to_string()).collect::<Vec<_>>()]);
+    }
+
+    #[test]
+    fn test_showdown_winners() {
+        let mut deps = mock_dependencies();
+        
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                players: test_players(3),
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        
+        // rig the dealt cards to get a known result
+        let mut table = load_table(deps.as_ref().storage, 1).unwrap();
+        let board = evaluator::tests::cards("Ah Kh 7c 4d 2s");
+        table.community_cards.flop.cards = board[..3].to_vec();
+        table.community_cards.turn.card = board[3].clone();
+        table.community_cards.river.card = board[4].clone();
+        table.players[0].hand = evaluator::tests::cards("Kc Kd");
+        table.players[1].hand = evaluator::tests::cards("Ac 7h");
+        table.players[2].hand = evaluator::tests::cards("As Ad");
+        save_table(deps.as_mut().storage, 1, &table).unwrap();
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Showdown {
+                table_id: 1,
+                game_state: GameState::PreFlop,
+                showdown_player_ids: vec![Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3)],
+                contributions: vec![],
+                pot: None,
+            },
+        )
+        .unwrap();
+        let response_attr = res.attributes.iter().find(|attr| attr.key == "response").unwrap();
+        let showdown = match serde_json_wasm::from_str(&response_attr.value).unwrap() {
+            ResponsePayload::Showdown(showdown) => showdown,
+            _ => panic!("Expected Showdown response"),
+        };
+        assert_eq!(showdown.results.len(), 1);
+        let result = &showdown.results[0];
+        assert_eq!(result.board, board);
+        assert_eq!(result.winners, vec![Uuid::from_u128(3)]);
+        let categories: Vec<_> = result.hands.iter().map(|hand| (hand.category, hand.kickers.clone())).collect();
+        assert_eq!(
+            categories,
+            vec![
+                (evaluator::HandCategory::ThreeOfAKind, vec![13, 14, 7]),
+                (evaluator::HandCategory::TwoPair, vec![14, 7, 13]),
+                (evaluator::HandCategory::ThreeOfAKind, vec![14, 13, 7]),
+            ]
+        );
+        
+        // a split pot reports every tied player
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                table_id: 2,
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        let mut table = load_table(deps.as_ref().storage, 2).unwrap();
+        let board = evaluator::tests::cards("5c 6d 7h 8s 9c");
+        table.community_cards.flop.cards = board[..3].to_vec();
+        table.community_cards.turn.card = board[3].clone();
+        table.community_cards.river.card = board[4].clone();
+        table.players[0].hand = evaluator::tests::cards("2c 2d");
+        table.players[1].hand = evaluator::tests::cards("Ah Kh");
+        save_table(deps.as_mut().storage, 2, &table).unwrap();
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Showdown {
+                table_id: 2,
+                game_state: GameState::PreFlop,
+                showdown_player_ids: vec![Uuid::from_u128(1), Uuid::from_u128(2)],
+                contributions: vec![],
+                pot: None,
+            },
+        )
+        .unwrap();
+        let response_attr = res.attributes.iter().find(|attr| attr.key == "response").unwrap();
+        match serde_json_wasm::from_str(&response_attr.value).unwrap() {
+            ResponsePayload::Showdown(showdown) => {
+                assert_eq!(showdown.results[0].winners, vec![Uuid::from_u128(1), Uuid::from_u128(2)]);
+                assert!(showdown.results[0].hands.iter().all(|hand| hand.category == evaluator::HandCategory::Straight));
+            }
+            _ => panic!("Expected Showdown response"),
+        }
+    }
+
+    #[test]
+    fn test_hi_lo_showdown() {
+        let mut deps = mock_dependencies();
+        
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        
+        // (board, hands, high winners, low winners)
+        let cases = [
+            // the board counterfeits the A-2, the 3-4 makes the wheel and scoops both halves
+            (
+                "Ah 2s 5c 7d 8h",
+                ["Ac 2d Kh Qs", "3c 4d Kc Kd", "8c 8d Js Ts"],
+                vec![2],
+                vec![2],
+            ),
+            // no three low cards on the board: the king-high straight takes the whole pot
+            (
+                "9c Td 2h Ks Qc",
+                ["Ac 2d 3h 4s", "Kc Kd 7s 6s", "Jc Jd 9h 8h"],
+                vec![3],
+                vec![],
+            ),
+            // the same low on both sides splits the low half
+            (
+                "3c 4d 5h Ks Qc",
+                ["Ac 2d Kh 9s", "Ad 2h Tc Td", "Qd Qh 7s 6s"],
+                vec![3],
+                vec![1, 2],
+            ),
+        ];
+        
+        for (table_id, (board, hands, high_winners, low_winners)) in cases.into_iter().enumerate() {
+            let table_id = table_id as u32 + 1;
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                NewHand {
+                    table_id,
+                    players: test_players(3),
+                    game_variant: GameVariant::Omaha8,
+                    ..NewHand::default()
+                }
+                .into(),
+            )
+            .unwrap();
+            assert!(res.attributes[0].value.contains("\"game_variant\":\"omaha8\""));
+            assert_eq!(query_player_private_data(deps.as_ref(), table_id, "key1".to_string()).unwrap().hand.len(), 4);
+            
+            let mut table = load_table(deps.as_ref().storage, table_id).unwrap();
+            let board = evaluator::tests::cards(board);
+            table.community_cards.flop.cards = board[..3].to_vec();
+            table.community_cards.turn.card = board[3].clone();
+            table.community_cards.river.card = board[4].clone();
+            for (player, hand) in table.players.iter_mut().zip(hands) {
+                player.hand = evaluator::tests::cards(hand);
+            }
+            save_table(deps.as_mut().storage, table_id, &table).unwrap();
+            
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::Showdown {
+                    table_id,
+                    game_state: GameState::PreFlop,
+                    showdown_player_ids: (1..=3).map(Uuid::from_u128).collect(),
+                    contributions: vec![],
+                    pot: None,
+                },
+            )
+            .unwrap();
+            let response_attr = res.attributes.iter().find(|attr| attr.key == "response").unwrap();
+            match serde_json_wasm::from_str(&response_attr.value).unwrap() {
+                ResponsePayload::Showdown(showdown) => {
+                    let result = &showdown.results[0];
+                    assert_eq!(result.winners, high_winners.into_iter().map(Uuid::from_u128).collect::<Vec<_>>());
+                    assert_eq!(result.low_winners, low_winners.into_iter().map(Uuid::from_u128).collect::<Vec<_>>());
+                }
+                _ => panic!("Expected Showdown response"),
+            }
+        }
+    }
+
+    fn start_betting_hand(deps: DepsMut, table_id: u32, stacks: &[u128], button: u32, escrow: Option<Currency>) -> Result<Response, ContractError> {
+        let players = test_players(stacks.len() as u128)
+            .into_iter()
+            .zip(stacks)
+            .map(|(player, &stack)| StartGamePlayer {
+                stack: Uint128::new(stack),
+                ..player
+            })
+            .collect();
+        execute(
+            deps,
+            mock_env(),
+            mock_info("creator", &coins(1000, "earth")),
+            NewHand {
+                table_id,
+                players,
+                betting: Some(BettingConfig {
+                    small_blind: Uint128::new(5),
+                    big_blind: Uint128::new(10),
+                    button,
+                    escrow,
+                }),
+                ..NewHand::default()
+            }
+            .into(),
+        )
+    }
+
+    fn act(deps: DepsMut, player: &str, msg: ExecuteMsg) -> Result<ActionResponse, ContractError> {
+        let res = execute(deps, mock_env(), mock_info(player, &[]), msg)?;
+        let response_attr = res.attributes.iter().find(|attr| attr.key == "response").unwrap();
+        match serde_json_wasm::from_str(&response_attr.value).unwrap() {
+            ResponsePayload::Action(action) => Ok(action),
+            _ => panic!("Expected Action response"),
+        }
+    }
+
+    #[test]
+    fn test_betting_round() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &coins(1000, "earth"));
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        let p2 = Uuid::from_u128(2);
+
+        // button on player1, player2 posts the small blind, player3 the big blind, player1 acts first
+        start_betting_hand(deps.as_mut(), 1, &[1000, 1000, 1000], 0, None).unwrap();
+
+        // the players sign their own actions, the owner can't act for them
+        for sender in ["creator", "key9"] {
+            let res = act(deps.as_mut(), sender, ExecuteMsg::Call { table_id: 1 });
+            assert_eq!(res.unwrap_err(), ContractError::PlayerNotFound { table_id: 1, player: sender.to_string() });
+        }
+
+        let res = act(deps.as_mut(), "key2", ExecuteMsg::Check { table_id: 1 });
+        assert!(matches!(res.unwrap_err(), ContractError::NotPlayerTurn { .. }));
+        let res = act(deps.as_mut(), "key1", ExecuteMsg::Check { table_id: 1 });
+        assert!(matches!(res.unwrap_err(), ContractError::InvalidAction { action: PlayerAction::Check, .. }));
+        let res = act(deps.as_mut(), "key1", ExecuteMsg::Raise { table_id: 1, amount: Uint128::new(15) });
+        assert_eq!(
+            res.unwrap_err(),
+            ContractError::InvalidBetAmount {
+                table_id: 1,
+                amount: Uint128::new(15),
+                min: Uint128::new(20),
+                max: Uint128::new(1000),
+            }
+        );
+
+        let action = act(deps.as_mut(), "key1", ExecuteMsg::Call { table_id: 1 }).unwrap();
+        assert_eq!(action.amount, Uint128::new(10));
+        assert_eq!(action.pot, Uint128::new(25));
+        assert_eq!(action.to_act, Some(p2));
+
+        // the streets follow the betting
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::CommunityCards { table_id: 1, game_state: GameState::Flop },
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            ContractError::BettingRoundOpen { table_id: 1, game_state: GameState::PreFlop }
+        );
+
+        act(deps.as_mut(), "key2", ExecuteMsg::Call { table_id: 1 }).unwrap();
+        let action = act(deps.as_mut(), "key3", ExecuteMsg::Check { table_id: 1 }).unwrap();
+        let table = load_table(deps.as_ref().storage, 1).unwrap();
+        assert_eq!(action.game_state, GameState::Flop);
+        assert_eq!(action.community_cards, table.community_cards.flop.cards);
+        assert_eq!(action.pot, Uint128::new(30));
+        assert_eq!(action.to_act, Some(p2));
+
+        let res = act(deps.as_mut(), "key2", ExecuteMsg::Bet { table_id: 1, amount: Uint128::new(5) });
+        assert!(matches!(res.unwrap_err(), ContractError::InvalidBetAmount { .. }));
+        act(deps.as_mut(), "key2", ExecuteMsg::Bet { table_id: 1, amount: Uint128::new(20) }).unwrap();
+        let res = act(deps.as_mut(), "key3", ExecuteMsg::Bet { table_id: 1, amount: Uint128::new(40) });
+        assert!(matches!(res.unwrap_err(), ContractError::InvalidAction { action: PlayerAction::Bet, .. }));
+        act(deps.as_mut(), "key3", ExecuteMsg::Raise { table_id: 1, amount: Uint128::new(60) }).unwrap();
+        act(deps.as_mut(), "key1", ExecuteMsg::Fold { table_id: 1 }).unwrap();
+        let action = act(deps.as_mut(), "key2", ExecuteMsg::Call { table_id: 1 }).unwrap();
+        assert_eq!(action.amount, Uint128::new(40));
+        assert_eq!(action.game_state, GameState::Turn);
+        assert_eq!(action.community_cards, vec![table.community_cards.turn.card.clone()]);
+        assert_eq!(action.pot, Uint128::new(150));
+
+        act(deps.as_mut(), "key2", ExecuteMsg::Check { table_id: 1 }).unwrap();
+        let action = act(deps.as_mut(), "key3", ExecuteMsg::Check { table_id: 1 }).unwrap();
+        assert_eq!(action.game_state, GameState::River);
+        assert_eq!(action.to_act, Some(p2));
+
+        act(deps.as_mut(), "key2", ExecuteMsg::Bet { table_id: 1, amount: Uint128::new(100) }).unwrap();
+        let action = act(deps.as_mut(), "key3", ExecuteMsg::Fold { table_id: 1 }).unwrap();
+        assert_eq!(action.winner, Some(p2));
+        assert_eq!(action.pot, Uint128::new(250));
+        assert_eq!(action.game_state, GameState::Closed);
+        assert_eq!(action.to_act, None);
+
+        let table = load_table(deps.as_ref().storage, 1).unwrap();
+        let stacks: Vec<_> = table.players.iter().map(|player| player.stack.u128()).collect();
+        assert_eq!(stacks, vec![990, 1080, 930]);
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Showdown { table_id: 1, game_state: GameState::River, showdown_player_ids: vec![p2], contributions: vec![], pot: None },
+        );
+        assert!(matches!(res.unwrap_err(), ContractError::GameStateError { .. }));
+    }
+
+    #[test]
+    fn test_all_in_betting() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &coins(1000, "earth"));
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        let (p1, p2) = (Uuid::from_u128(1), Uuid::from_u128(2));
+
+        assert_eq!(
+            start_betting_hand(deps.as_mut(), 1, &[500, 0], 0, None).unwrap_err(),
+            ContractError::EmptyStack { player: p2.to_string() }
+        );
+
+        // heads-up the button posts the small blind and acts first preflop
+        start_betting_hand(deps.as_mut(), 1, &[500, 1000], 0, None).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Showdown { table_id: 1, game_state: GameState::PreFlop, showdown_player_ids: vec![p1, p2], contributions: vec![], pot: None },
+        );
+        assert!(matches!(res.unwrap_err(), ContractError::BettingRoundOpen { .. }));
+
+        let action = act(deps.as_mut(), "key1", ExecuteMsg::AllIn { table_id: 1 }).unwrap();
+        assert_eq!(action.amount, Uint128::new(495));
+        assert_eq!(action.to_act, Some(p2));
+
+        // nobody is left to bet against, the hand waits for its showdown on the street it was played
+        let action = act(deps.as_mut(), "key2", ExecuteMsg::Call { table_id: 1 }).unwrap();
+        assert_eq!(action.amount, Uint128::new(490));
+        assert_eq!(action.pot, Uint128::new(1000));
+        assert_eq!(action.game_state, GameState::PreFlop);
+        assert_eq!(action.to_act, None);
+        assert!(action.community_cards.is_empty());
+
+        let res = act(deps.as_mut(), "key2", ExecuteMsg::Check { table_id: 1 });
+        assert!(matches!(res.unwrap_err(), ContractError::NotPlayerTurn { .. }));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Showdown { table_id: 1, game_state: GameState::PreFlop, showdown_player_ids: vec![p1, p2], contributions: vec![], pot: None },
+        )
+        .unwrap();
+
+        // a table without on-chain betting rejects the actions
+        start_betting_hand(deps.as_mut(), 2, &[500, 500], 1, None).unwrap();
+        let mut table = load_table(deps.as_ref().storage, 2).unwrap();
+        table.betting = None;
+        save_table(deps.as_mut().storage, 2, &table).unwrap();
+        let res = act(deps.as_mut(), "key2", ExecuteMsg::Fold { table_id: 2 });
+        assert_eq!(res.unwrap_err(), ContractError::BettingDisabled { table_id: 2 });
+    }
+
+    #[test]
+    fn test_incomplete_all_in_raise() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &coins(1000, "earth"));
+        instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        let p1 = Uuid::from_u128(1);
+
+        // button on player3: player1 small blind, player2 big blind, player3 first to act
+        start_betting_hand(deps.as_mut(), 1, &[1000, 1000, 60], 2, None).unwrap();
+        act(deps.as_mut(), "key3", ExecuteMsg::Raise { table_id: 1, amount: Uint128::new(30) }).unwrap();
+        let res = act(deps.as_mut(), "key1", ExecuteMsg::Raise { table_id: 1, amount: Uint128::new(40) });
+        assert!(matches!(res.unwrap_err(), ContractError::InvalidBetAmount { min, .. } if min == Uint128::new(50)));
+        act(deps.as_mut(), "key1", ExecuteMsg::Call { table_id: 1 }).unwrap();
+        act(deps.as_mut(), "key2", ExecuteMsg::Call { table_id: 1 }).unwrap();
+
+        // flop: player3's all-in for 30 is not a full raise over player2's 20 bet, the next raise is still measured from 20
+        act(deps.as_mut(), "key1", ExecuteMsg::Check { table_id: 1 }).unwrap();
+        act(deps.as_mut(), "key2", ExecuteMsg::Bet { table_id: 1, amount: Uint128::new(20) }).unwrap();
+        act(deps.as_mut(), "key3", ExecuteMsg::AllIn { table_id: 1 }).unwrap();
+        let res = act(deps.as_mut(), "key1", ExecuteMsg::Raise { table_id: 1, amount: Uint128::new(45) });
+        assert!(matches!(res.unwrap_err(), ContractError::InvalidBetAmount { min, .. } if min == Uint128::new(50)));
+        act(deps.as_mut(), "key1", ExecuteMsg::Call { table_id: 1 }).unwrap();
+
+        // nor does it reopen the betting, player2 already acted on the 20 and can only call or fold
+        for msg in [ExecuteMsg::Raise { table_id: 1, amount: Uint128::new(50) }, ExecuteMsg::AllIn { table_id: 1 }] {
+            let res = act(deps.as_mut(), "key2", msg);
+            assert!(matches!(res.unwrap_err(), ContractError::InvalidAction { .. }));
+        }
+        let action = act(deps.as_mut(), "key2", ExecuteMsg::Call { table_id: 1 }).unwrap();
+        assert_eq!(action.game_state, GameState::Turn);
+        assert_eq!(action.pot, Uint128::new(90 + 30 + 30 + 30));
+        assert_eq!(action.to_act, Some(p1));
+    }
+
+    fn rig_hand(deps: DepsMut, table_id: u32, board: &str, hands: &[&str]) {
+        let mut table = load_table(deps.storage, table_id).unwrap();
+        let board = evaluator::tests::cards(board);
+        table.community_cards.flop.cards = board[..3].to_vec();
+        table.community_cards.turn.card = board[3].clone();
+        table.community_cards.river.card = board[4].clone();
+        for (player, hand) in table.players.iter_mut().zip(hands) {
+            player.hand = evaluator::tests::cards(hand);
+        }
+        save_table(deps.storage, table_id, &table).unwrap();
+    }
+
+    fn showdown_response(res: Response) -> ShowdownResponse {
+        let response_attr = res.attributes.iter().find(|attr| attr.key == "response").unwrap();
+        match serde_json_wasm::from_str(&response_attr.value).unwrap() {
+            ResponsePayload::Showdown(showdown) => showdown,
+            _ => panic!("Expected Showdown response"),
+        }
+    }
+
+    fn payout(player: u128, amount: u128) -> Payout {
+        Payout { player_id: Uuid::from_u128(player), amount: Uint128::new(amount) }
+    }
+
+    #[test]
+    fn test_three_way_all_in_side_pot() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &coins(1000, "earth"));
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        let (p1, p2, p3) = (Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3));
+
+        start_betting_hand(deps.as_mut(), 1, &[55, 120, 200], 0, None).unwrap();
+        act(deps.as_mut(), "key1", ExecuteMsg::AllIn { table_id: 1 }).unwrap();
+        act(deps.as_mut(), "key2", ExecuteMsg::AllIn { table_id: 1 }).unwrap();
+        let action = act(deps.as_mut(), "key3", ExecuteMsg::Call { table_id: 1 }).unwrap();
+        assert_eq!(action.to_act, None);
+
+        // player1 and player3 chop the main pot with the same ace-queen, player2 has nothing
+        rig_hand(deps.as_mut(), 1, "2c 7d 9h Jc Ks", &["As Qd", "3d 4s", "Ah Qc"]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Showdown {
+                table_id: 1,
+                game_state: GameState::PreFlop,
+                showdown_player_ids: vec![p1, p2, p3],
+                contributions: vec![], // ignored, the chips were committed on-chain
+                pot: None,
+            },
+        )
+        .unwrap();
+        let showdown = showdown_response(res);
+
+        assert_eq!(
+            showdown.pots,
+            vec![
+                // 165 does not split evenly, the odd chip goes to player3, the first winner left of the button
+                PotResult { amount: Uint128::new(165), eligible: vec![p2, p3, p1], payouts: vec![payout(3, 83), payout(1, 82)] },
+                PotResult { amount: Uint128::new(130), eligible: vec![p2, p3], payouts: vec![payout(3, 130)] },
+            ]
+        );
+        assert_eq!(showdown.uncalled, vec![]);
+        assert_eq!(showdown.payouts, vec![payout(3, 213), payout(1, 82)]);
+
+        let table = load_table(deps.as_ref().storage, 1).unwrap();
+        let stacks: Vec<_> = table.players.iter().map(|player| player.stack.u128()).collect();
+        assert_eq!(stacks, vec![82, 0, 293]);
+    }
+
+    #[test]
+    fn test_four_way_all_in_side_pots() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &coins(1000, "earth"));
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        let (p1, p2, p3, p4) = (Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3), Uuid::from_u128(4));
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                players: test_players(4),
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        // player1 makes a flush, player2 and player3 split with the same straight, player4 folded
+        rig_hand(deps.as_mut(), 1, "5h 6h 7h 8c Kd", &["Ah 2h", "9c 4d", "9d 4s", "Ks Kc"]);
+
+        let contribution = |player_id: Uuid, amount: u128| Contribution { player_id, amount: Uint128::new(amount) };
+        let showdown_msg = |contributions| ExecuteMsg::Showdown {
+            table_id: 1,
+            game_state: GameState::PreFlop,
+            showdown_player_ids: vec![p1, p2, p3],
+            contributions,
+            pot: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            showdown_msg(vec![contribution(p1, 25), contribution(Uuid::from_u128(9), 10)]),
+        );
+        assert!(matches!(res.unwrap_err(), ContractError::PlayerNotFound { .. }));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            showdown_msg(vec![contribution(p4, 30), contribution(p2, 101), contribution(p1, 25), contribution(p3, 120)]),
+        )
+        .unwrap();
+        let showdown = showdown_response(res);
+
+        assert_eq!(
+            showdown.pots,
+            vec![
+                PotResult { amount: Uint128::new(100), eligible: vec![p1, p2, p3], payouts: vec![payout(1, 100)] },
+                // 157 is split between the straights, the odd chip goes to player2 in seat order
+                PotResult { amount: Uint128::new(157), eligible: vec![p2, p3], payouts: vec![payout(2, 79), payout(3, 78)] },
+            ]
+        );
+        assert_eq!(showdown.uncalled, vec![payout(3, 19)]);
+        assert_eq!(showdown.payouts, vec![payout(1, 100), payout(2, 79), payout(3, 97)]);
+    }
+
+    #[test]
+    fn test_short_deck_showdown() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &coins(1000, "earth"));
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        let (p1, p2, p3) = (Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3));
+
+        // the same cards on a short deck table and on a standard one
+        for (table_id, deck_type) in [(1, DeckType::ShortDeck), (2, DeckType::Standard)] {
+            let hand = NewHand { table_id, players: test_players(3), deck_type, ..NewHand::default() };
+            execute(deps.as_mut(), mock_env(), info.clone(), hand.into()).unwrap();
+            // player1 has A-6-7-8-9, player2 a flush and player3 a full house
+            rig_hand(deps.as_mut(), table_id, "Kh 7h 8c 9d 9h", &["As 6c", "Ah Jh", "Ks Kc"]);
+        }
+        let results = [1, 2].map(|table_id| {
+            let msg = ExecuteMsg::Showdown {
+                table_id,
+                game_state: GameState::PreFlop,
+                showdown_player_ids: vec![p1, p2, p3],
+                contributions: vec![],
+                pot: None,
+            };
+            let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+            showdown_response(res).results.remove(0)
+        });
+        let categories = |result: &BoardResult| result.hands.iter().map(|hand| hand.category).collect::<Vec<_>>();
+        let [short_deck, standard] = &results;
+        use evaluator::HandCategory::*;
+
+        assert_eq!(categories(short_deck), vec![Straight, Flush, FullHouse]);
+        assert_eq!(short_deck.hands[0].kickers, vec![9]);
+        assert_eq!(short_deck.winners, vec![p2]);
+
+        assert_eq!(categories(standard), vec![OnePair, Flush, FullHouse]);
+        assert_eq!(standard.winners, vec![p3]);
+    }
+
+    #[test]
+    fn test_showdown_undealt_hand() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &coins(1000, "earth"));
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        let hand = NewHand { game_variant: GameVariant::Omaha4, ..NewHand::default() };
+        execute(deps.as_mut(), mock_env(), info.clone(), hand.into()).unwrap();
+        let mut table = load_table(deps.as_ref().storage, 1).unwrap();
+        table.players[1].hand = vec![];
+        save_table(deps.as_mut().storage, 1, &table).unwrap();
+
+        // player2's hole cards are missing, and an Omaha hand must use two of them
+        let msg = ExecuteMsg::Showdown {
+            table_id: 1,
+            game_state: GameState::PreFlop,
+            showdown_player_ids: vec![Uuid::from_u128(1), Uuid::from_u128(2)],
+            contributions: vec![],
+            pot: None,
+        };
+        assert_eq!(
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap_err(),
+            ContractError::InvalidHand { table_id: 1, player: Uuid::from_u128(2).to_string() }
+        );
+    }
+
+    const TOKEN: &str = "token";
+    const TOKEN_CODE_HASH: &str = "token_code_hash";
+
+    fn mock_token() -> TokenContractInfo {
+        TokenContractInfo {
+            address: TOKEN.to_string(),
+            code_hash: TOKEN_CODE_HASH.to_string(),
+        }
+    }
+
+    // the message the mock token contract would receive
+    fn token_msg(msg: &CosmosMsg) -> snip20::Snip20ExecuteMsg {
+        match msg {
+            CosmosMsg::Wasm(cosmwasm_std::WasmMsg::Execute { contract_addr, code_hash, msg, .. }) => {
+                assert_eq!((contract_addr.as_str(), code_hash.as_str()), (TOKEN, TOKEN_CODE_HASH));
+                from_binary(msg).unwrap()
+            }
+            _ => panic!("Expected a token message"),
+        }
+    }
+
+    fn transfer(recipient: &str, amount: u128) -> snip20::Snip20ExecuteMsg {
+        snip20::Snip20ExecuteMsg::Transfer {
+            recipient: recipient.to_string(),
+            amount: Uint128::new(amount),
+            memo: None,
+            padding: None,
+        }
+    }
+
+    fn buy_in(table_id: u32) -> Option<Binary> {
+        Some(to_binary(&ReceiveMsg::BuyIn { table_id }).unwrap())
+    }
+
+    #[test]
+    fn test_token_escrow() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let res = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            InstantiateMsg { accepted_token: Some(mock_token()), rake: None },
+        )
+        .unwrap();
+        assert_eq!(
+            token_msg(&res.messages[0].msg),
+            snip20::Snip20ExecuteMsg::RegisterReceive { code_hash: mock_env().contract.code_hash, padding: None }
+        );
+
+        let receive = |from: &str, amount: u128, msg: Option<Binary>| ExecuteMsg::Receive {
+            sender: from.to_string(),
+            from: from.to_string(),
+            amount: Uint128::new(amount),
+            memo: None,
+            msg,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("fake_token", &[]), receive("key1", 150, buy_in(1)));
+        assert_eq!(res.unwrap_err(), ContractError::UnsupportedToken { token: "fake_token".to_string() });
+        let res = execute(deps.as_mut(), mock_env(), mock_info(TOKEN, &[]), receive("key1", 150, None));
+        assert_eq!(res.unwrap_err(), ContractError::InvalidReceiveMsg {});
+        for player in ["key1", "key2"] {
+            execute(deps.as_mut(), mock_env(), mock_info(TOKEN, &[]), receive(player, 150, buy_in(1))).unwrap();
+        }
+        execute(deps.as_mut(), mock_env(), mock_info(TOKEN, &[]), receive("key1", 10, buy_in(2))).unwrap();
+        assert_eq!(load_balance(deps.as_ref().storage, 1, &Currency::Token, "key1"), Uint128::new(150));
+        assert_eq!(load_balance(deps.as_ref().storage, 2, &Currency::Token, "key1"), Uint128::new(10));
+
+        assert_eq!(
+            start_betting_hand(deps.as_mut(), 1, &[100, 200], 0, Some(Currency::Token)).unwrap_err(),
+            ContractError::InsufficientBalance { table_id: 1, player: "key2".to_string(), balance: Uint128::new(150) }
+        );
+        start_betting_hand(deps.as_mut(), 1, &[100, 100], 0, Some(Currency::Token)).unwrap();
+        assert_eq!(load_balance(deps.as_ref().storage, 1, &Currency::Token, "key1"), Uint128::new(50));
+
+        // player1 folds the small blind, player2 is paid the called pot and both get their chips back in escrow,
+        // the 5 chips of the big blind nobody called included
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("key1", &[]),
+            ExecuteMsg::Fold { table_id: 1 },
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(token_msg(&res.messages[0].msg), transfer("key2", 10));
+        assert_eq!(load_balance(deps.as_ref().storage, 1, &Currency::Token, "key1"), Uint128::new(145));
+        assert_eq!(load_balance(deps.as_ref().storage, 1, &Currency::Token, "key2"), Uint128::new(145));
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("key1", &[]), ExecuteMsg::Withdraw { table_id: 1 }).unwrap();
+        assert_eq!(token_msg(&res.messages[0].msg), transfer("key1", 145));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("key1", &[]), ExecuteMsg::Withdraw { table_id: 1 });
+        assert!(matches!(res.unwrap_err(), ContractError::InsufficientBalance { .. }));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("key1", &[]),
+            ExecuteMsg::SetAcceptedToken { token: None },
+        );
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+        // player2 still has 145 in escrow at table 1 and player1 10 at table 2
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), ExecuteMsg::SetAcceptedToken { token: None });
+        assert_eq!(res.unwrap_err(), ContractError::TokenHeld { amount: Uint128::new(155) });
+        execute(deps.as_mut(), mock_env(), info.clone(), ExecuteMsg::SetAcceptedToken { token: Some(mock_token()) }).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("key2", &[]), ExecuteMsg::Withdraw { table_id: 1 }).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), ExecuteMsg::SetAcceptedToken { token: None });
+        assert_eq!(res.unwrap_err(), ContractError::TokenHeld { amount: Uint128::new(10) });
+        execute(deps.as_mut(), mock_env(), mock_info("key1", &[]), ExecuteMsg::Withdraw { table_id: 2 }).unwrap();
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::SetAcceptedToken { token: None }).unwrap();
+        assert_eq!(
+            start_betting_hand(deps.as_mut(), 2, &[10, 10], 0, Some(Currency::Token)).unwrap_err(),
+            ContractError::NoAcceptedToken {}
+        );
+    }
+
+    #[test]
+    fn test_escrow_showdown_payouts() {
         let mut deps = mock_dependencies();
-
-        let msg = InstantiateMsg {};
         let info = mock_info("creator", &coins(1000, "earth"));
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg { accepted_token: Some(mock_token()), rake: None }).unwrap();
+        for (player, amount) in [("key1", 55), ("key2", 120), ("key3", 300)] {
+            let msg = ExecuteMsg::Receive {
+                sender: player.to_string(),
+                from: player.to_string(),
+                amount: Uint128::new(amount),
+                memo: None,
+                msg: buy_in(1),
+            };
+            execute(deps.as_mut(), mock_env(), mock_info(TOKEN, &[]), msg).unwrap();
+        }
 
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
+        let (p1, p2, p3) = (Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3));
+        start_betting_hand(deps.as_mut(), 1, &[55, 120, 200], 0, Some(Currency::Token)).unwrap();
+        act(deps.as_mut(), "key1", ExecuteMsg::AllIn { table_id: 1 }).unwrap();
+        act(deps.as_mut(), "key2", ExecuteMsg::AllIn { table_id: 1 }).unwrap();
+        act(deps.as_mut(), "key3", ExecuteMsg::Call { table_id: 1 }).unwrap();
+        rig_hand(deps.as_mut(), 1, "2c 7d 9h Jc Ks", &["As Qd", "3d 4s", "Ah Qc"]);
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Showdown {
+                table_id: 1,
+                game_state: GameState::PreFlop,
+                showdown_player_ids: vec![p1, p2, p3],
+                contributions: vec![],
+                pot: None,
+            },
+        )
+        .unwrap();
+        let transfers: Vec<_> = res.messages.iter().map(|msg| token_msg(&msg.msg)).collect();
+        assert_eq!(transfers, vec![transfer("key1", 82), transfer("key3", 213)]);
+        // player3 kept 80 of the 200 brought to the hand, on top of the 100 left in escrow
+        assert_eq!(load_balance(deps.as_ref().storage, 1, &Currency::Token, "key3"), Uint128::new(180));
+        assert_eq!(load_balance(deps.as_ref().storage, 1, &Currency::Token, "key2"), Uint128::zero());
     }
 
     #[test]
-    fn test_start_game() {
+    fn test_native_buy_in() {
         let mut deps = mock_dependencies();
-
-        let msg = InstantiateMsg {};
         let info = mock_info("creator", &coins(1000, "earth"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        let (p1, p2) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let native_balance = |deps: Deps, player: &str| load_balance(deps.storage, 1, &Currency::Native, player).u128();
 
-        let players = vec![
-            StartGamePlayer {
-                username: "player1".to_string(),
-                player_id: Uuid::parse_str("2928c53b-5d14-4a7c-b56e-83ef56a0644e").unwrap(),
-                public_key: "key1".to_string(),
-            },
-            StartGamePlayer {
-                username: "player2".to_string(),
-                player_id: Uuid::parse_str("8f204fcc-54a5-4473-8ac3-4845bff291ab").unwrap(),
-                public_key: "key2".to_string(),
-            },
-        ];
+        for funds in [vec![], coins(100, "earth"), vec![Coin::new(100, "uscrt"), Coin::new(100, "earth")]] {
+            let res = execute(deps.as_mut(), mock_env(), mock_info("key1", &funds), ExecuteMsg::JoinTable { table_id: 1 });
+            assert_eq!(res.unwrap_err(), ContractError::InvalidFunds { denom: "uscrt".to_string() });
+        }
+        for player in ["key1", "key2"] {
+            execute(deps.as_mut(), mock_env(), mock_info(player, &coins(150, "uscrt")), ExecuteMsg::JoinTable { table_id: 1 }).unwrap();
+        }
+        execute(deps.as_mut(), mock_env(), mock_info("key1", &coins(50, "uscrt")), ExecuteMsg::JoinTable { table_id: 1 }).unwrap();
+        assert_eq!(native_balance(deps.as_ref(), "key1"), 200);
+        // the token balance is kept apart
+        assert_eq!(load_balance(deps.as_ref().storage, 1, &Currency::Token, "key1"), Uint128::zero());
+
+        start_betting_hand(deps.as_mut(), 1, &[100, 100], 0, Some(Currency::Native)).unwrap();
+        assert_eq!(native_balance(deps.as_ref(), "key1"), 100);
+        assert_eq!(native_balance(deps.as_ref(), "key2"), 50);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("key2", &[]), ExecuteMsg::LeaveTable { table_id: 1 });
+        assert_eq!(res.unwrap_err(), ContractError::PlayerInHand { table_id: 1, player: "key2".to_string() });
 
+        act(deps.as_mut(), "key1", ExecuteMsg::AllIn { table_id: 1 }).unwrap();
+        act(deps.as_mut(), "key2", ExecuteMsg::Call { table_id: 1 }).unwrap();
+        rig_hand(deps.as_mut(), 1, "2c 7d 9h Jc Ks", &["3d 4s", "As Qd"]);
         let res = execute(
             deps.as_mut(),
             mock_env(),
             info,
-            ExecuteMsg::StartGame {
+            ExecuteMsg::Showdown {
                 table_id: 1,
-                hand_ref: 1,
-                players,
-                prev_hand_showdown_players: vec![],
+                game_state: GameState::PreFlop,
+                showdown_player_ids: vec![p1, p2],
+                contributions: vec![],
+                pot: None,
             },
         )
         .unwrap();
-        
-        let attrs = &res.attributes;
-        let response_attr = attrs.iter().find(|attr| attr.key == "response").unwrap();
-        assert!(response_attr.value.contains("\"players\":[\"player1\",\"player2\"]"));
-        assert!(response_attr.value.contains("\"table_id\":1"));
-        assert!(response_attr.value.contains("\"hand_ref\":1"));
+        assert_eq!(
+            res.messages.iter().map(|msg| msg.msg.clone()).collect::<Vec<_>>(),
+            vec![CosmosMsg::Bank(BankMsg::Send { to_address: "key2".to_string(), amount: coins(200, "uscrt") })]
+        );
 
-        let player_info1 = query_player_private_data(deps.as_ref(), 1, "key1".to_string()).unwrap();
-        let player_info2 = query_player_private_data(deps.as_ref(), 1, "key2".to_string()).unwrap();
-        
-        
-        assert_eq!(player_info1.table_id, 1);
-        assert_eq!(player_info1.hand_ref, 1);
-        assert_eq!(player_info1.hand.len(), 2);
-        assert!(player_info1.flop_secret_share.parse::<u64>().is_ok());
-        
-        assert_eq!(player_info2.table_id, 1);
-        assert_eq!(player_info2.hand_ref, 1);
-        assert_eq!(player_info2.hand.len(), 2);
-        assert!(player_info2.flop_secret_share.parse::<u64>().is_ok());
-        
-        let flop_secret = addition_shares(vec![
-            player_info1.flop_secret_share.parse::<u64>().unwrap(),
-            player_info2.flop_secret_share.parse::<u64>().unwrap(),
-        ]);
-        let turn_secret = addition_shares(vec![
-            player_info1.turn_secret_share.parse::<u64>().unwrap(),
-            player_info2.turn_secret_share.parse::<u64>().unwrap(),
-        ]);
-        let river_secret = addition_shares(vec![
-            player_info1.river_secret_share.parse::<u64>().unwrap(),
-            player_info2.river_secret_share.parse::<u64>().unwrap(),
-        ]);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("key2", &[]), ExecuteMsg::LeaveTable { table_id: 1 }).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "key2".to_string(), amount: coins(50, "uscrt") })
+        );
+        let res = execute(deps.as_mut(), mock_env(), mock_info("key2", &[]), ExecuteMsg::LeaveTable { table_id: 1 });
+        assert!(matches!(res.unwrap_err(), ContractError::InsufficientBalance { .. }));
+        assert_eq!(native_balance(deps.as_ref(), "key1"), 100);
+    }
 
-        
-        let res = query(
-            deps.as_ref(),
+    #[test]
+    fn test_rake() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let rake = RakeConfig { basis_points: 500, cap: Uint128::new(15) };
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg { accepted_token: None, rake: Some(rake) }).unwrap();
+        let res = execute(
+            deps.as_mut(),
             mock_env(),
-            QueryMsg::CommunityCards {
-                table_id: 1,
-                game_state: GameState::Flop,
-                secret_key: flop_secret,
-            },
+            info.clone(),
+            ExecuteMsg::SetRake { rake: Some(RakeConfig { basis_points: 10_001, cap: Uint128::new(15) }) },
         );
-        let flop_response: CommunityCardsResponse = from_binary(res.as_ref().unwrap()).unwrap();
-        assert_eq!(flop_response.table_id, 1);
-        assert_eq!(flop_response.hand_ref, 1);
-        assert_eq!(flop_response.game_state, GameState::Flop);
-        assert_eq!(flop_response.community_cards.len(), 3);
+        assert_eq!(res.unwrap_err(), ContractError::InvalidRake { basis_points: 10_001 });
+        let (p1, p2) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        for table_id in 1..=3 {
+            for player in ["key1", "key2"] {
+                execute(deps.as_mut(), mock_env(), mock_info(player, &coins(100, "uscrt")), ExecuteMsg::JoinTable { table_id }).unwrap();
+            }
+            start_betting_hand(deps.as_mut(), table_id, &[100, 100], 0, Some(Currency::Native)).unwrap();
+        }
 
-        
-        let res = query(
-            deps.as_ref(),
+        // 5% of the 200 chips pot, under the cap
+        act(deps.as_mut(), "key1", ExecuteMsg::AllIn { table_id: 1 }).unwrap();
+        act(deps.as_mut(), "key2", ExecuteMsg::Call { table_id: 1 }).unwrap();
+        rig_hand(deps.as_mut(), 1, "2c 7d 9h Jc Ks", &["3d 4s", "As Qd"]);
+        let res = execute(
+            deps.as_mut(),
             mock_env(),
-            QueryMsg::CommunityCards {
+            info.clone(),
+            ExecuteMsg::Showdown {
                 table_id: 1,
-                game_state: GameState::Turn,
-                secret_key: turn_secret,
+                game_state: GameState::PreFlop,
+                showdown_player_ids: vec![p1, p2],
+                contributions: vec![],
+                pot: None,
             },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "key2".to_string(), amount: coins(190, "uscrt") })
         );
-        let turn_response: CommunityCardsResponse = from_binary(res.as_ref().unwrap()).unwrap();
-        assert_eq!(turn_response.table_id, 1);
-        assert_eq!(turn_response.hand_ref, 1);
-        assert_eq!(turn_response.game_state, GameState::Turn);
-        assert_eq!(turn_response.community_cards.len(), 1);
+        let showdown = showdown_response(res);
+        assert_eq!(showdown.rake, Uint128::new(10));
+        assert_eq!(showdown.pots[0].amount, Uint128::new(190));
+        assert_eq!(showdown.payouts, vec![payout(2, 190)]);
 
-        
-        let res = query(
-            deps.as_ref(),
-            mock_env(),
-            QueryMsg::CommunityCards {
-                table_id: 1,
-                game_state: GameState::River,
-                secret_key: river_secret,
-            },
+        // no flop no drop
+        let action = act(deps.as_mut(), "key1", ExecuteMsg::Fold { table_id: 2 }).unwrap();
+        assert_eq!((action.winner, action.rake), (Some(p2), Uint128::zero()));
+
+        // the flop was dealt before player1 folded to the bet, the 20 chips bet are uncalled, not raked and
+        // go back to player2's balance: 5% of the 20 chips pot
+        act(deps.as_mut(), "key1", ExecuteMsg::Call { table_id: 3 }).unwrap();
+        act(deps.as_mut(), "key2", ExecuteMsg::Check { table_id: 3 }).unwrap();
+        act(deps.as_mut(), "key2", ExecuteMsg::Bet { table_id: 3, amount: Uint128::new(20) }).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), mock_info("key1", &[]), ExecuteMsg::Fold { table_id: 3 }).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "key2".to_string(), amount: coins(19, "uscrt") })
         );
-        let river_response: CommunityCardsResponse = from_binary(res.as_ref().unwrap()).unwrap();
-        assert_eq!(river_response.table_id, 1);
-        assert_eq!(river_response.hand_ref, 1);
-        assert_eq!(river_response.game_state, GameState::River);
-        assert_eq!(river_response.community_cards.len(), 1);
+        assert_eq!(load_balance(deps.as_ref().storage, 3, &Currency::Native, "key2"), Uint128::new(90));
+        assert_eq!(TREASURY.get(deps.as_ref().storage, &Currency::Native), Some(Uint128::new(11)));
 
-        
-        let res = query(
-            deps.as_ref(),
+        let res = execute(deps.as_mut(), mock_env(), mock_info("key2", &[]), ExecuteMsg::WithdrawRake { recipient: None });
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+        let msg = ExecuteMsg::WithdrawRake { recipient: Some("house".to_string()) };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send { to_address: "house".to_string(), amount: coins(11, "uscrt") })
+        );
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        assert_eq!(res.unwrap_err(), ContractError::EmptyTreasury {});
+    }
+
+    #[test]
+    fn test_rake_empty_showdown() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let rake = RakeConfig { basis_points: 500, cap: Uint128::new(15) };
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg { accepted_token: None, rake: Some(rake) }).unwrap();
+        for player in ["key1", "key2"] {
+            execute(deps.as_mut(), mock_env(), mock_info(player, &coins(150, "uscrt")), ExecuteMsg::JoinTable { table_id: 1 }).unwrap();
+        }
+        start_betting_hand(deps.as_mut(), 1, &[100, 80], 0, Some(Currency::Native)).unwrap();
+        act(deps.as_mut(), "key1", ExecuteMsg::AllIn { table_id: 1 }).unwrap();
+        act(deps.as_mut(), "key2", ExecuteMsg::Call { table_id: 1 }).unwrap();
+
+        // nobody shows: the supplied pot is ignored and every player gets the chips committed back in escrow
+        let res = execute(
+            deps.as_mut(),
             mock_env(),
-            QueryMsg::CommunityCards {
+            info,
+            ExecuteMsg::Showdown {
                 table_id: 1,
-                game_state: GameState::Flop,
-                secret_key: flop_secret + 1, 
+                game_state: GameState::PreFlop,
+                showdown_player_ids: vec![],
+                contributions: vec![],
+                pot: Some(Uint128::new(1000)),
             },
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        let showdown = showdown_response(res);
+        assert_eq!((showdown.rake, showdown.pots), (Uint128::zero(), vec![]));
+        assert_eq!(TREASURY.get(deps.as_ref().storage, &Currency::Native), None);
+        for (player, balance) in [("key1", 150), ("key2", 150)] {
+            assert_eq!(load_balance(deps.as_ref().storage, 1, &Currency::Native, player), Uint128::new(balance));
+        }
+    }
+
+    #[test]
+    fn test_rake_off_chain() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let rake = RakeConfig { basis_points: 500, cap: Uint128::new(15) };
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg { accepted_token: None, rake: Some(rake) }).unwrap();
+        let (p1, p2) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        for table_id in [1, 2] {
+            execute(deps.as_mut(), mock_env(), info.clone(), NewHand { table_id, ..NewHand::default() }.into()).unwrap();
+        }
+
+        // the hands are shown without contributions, nothing caps the rake of the supplied pot
+        for (table_id, pot, rake) in [(1, 200, 10), (2, 1000, 15)] {
+            let msg = ExecuteMsg::Showdown {
+                table_id,
+                game_state: GameState::PreFlop,
+                showdown_player_ids: vec![p1, p2],
+                contributions: vec![],
+                pot: Some(Uint128::new(pot)),
+            };
+            let showdown = showdown_response(execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap());
+            assert_eq!((showdown.rake, showdown.pots), (Uint128::new(rake), vec![]));
+        }
+
+        // the chips never were in the contract, the rake is only reported
+        assert_eq!(TREASURY.get(deps.as_ref().storage, &Currency::Native), None);
+        let msg = ExecuteMsg::WithdrawRake { recipient: None };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        assert!(res.messages.is_empty());
+        let off_chain = res.attributes.iter().find(|attr| attr.key == "off_chain_rake").unwrap();
+        assert_eq!(off_chain.value, "25");
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        assert_eq!(res.unwrap_err(), ContractError::EmptyTreasury {});
+    }
+
+    #[test]
+    fn test_operators() {
+        let mut deps = mock_dependencies();
+        let owner = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        let operator = mock_info("operator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), operator.clone(), start_game_msg(1, 1));
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+        let add_operator = ExecuteMsg::AddOperator { address: "operator".to_string() };
+        execute(deps.as_mut(), mock_env(), owner.clone(), add_operator.clone()).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), add_operator);
+        assert_eq!(res.unwrap_err(), ContractError::OperatorExists { operator: "operator".to_string() });
+        execute(deps.as_mut(), mock_env(), operator.clone(), start_game_msg(1, 1)).unwrap();
+        execute(deps.as_mut(), mock_env(), operator.clone(), ExecuteMsg::CommunityCards { table_id: 1, game_state: GameState::Flop }).unwrap();
+        // the configuration stays with the owner
+        let res = execute(deps.as_mut(), mock_env(), operator.clone(), ExecuteMsg::AddOperator { address: "other".to_string() });
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+        let config: ConfigResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.operators, vec![Addr::unchecked("operator")]);
+
+        let remove_operator = ExecuteMsg::RemoveOperator { address: "operator".to_string() };
+        execute(deps.as_mut(), mock_env(), owner.clone(), remove_operator.clone()).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), remove_operator);
+        assert_eq!(res.unwrap_err(), ContractError::OperatorNotFound { operator: "operator".to_string() });
+        let res = execute(deps.as_mut(), mock_env(), operator, start_game_msg(2, 1));
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+    }
+
+    #[test]
+    fn test_table_operators() {
+        let mut deps = mock_dependencies();
+        let owner = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        let (casino1, casino2) = (mock_info("casino1", &[]), mock_info("casino2", &[]));
+        for operator in ["casino1", "casino2"] {
+            execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::AddOperator { address: operator.to_string() }).unwrap();
+        }
+        execute(deps.as_mut(), mock_env(), casino1.clone(), start_game_msg(1, 1)).unwrap();
+        execute(deps.as_mut(), mock_env(), casino2.clone(), start_game_msg(2, 1)).unwrap();
+
+        // casino2 can't run the table of casino1
+        let flop = ExecuteMsg::CommunityCards { table_id: 1, game_state: GameState::Flop };
+        let showdown = ExecuteMsg::Showdown {
+            table_id: 1,
+            game_state: GameState::River,
+            showdown_player_ids: vec![],
+            contributions: vec![],
+            pot: None,
+        };
+        for msg in [start_game_msg(1, 2), flop.clone(), showdown] {
+            let res = execute(deps.as_mut(), mock_env(), casino2.clone(), msg);
+            assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+        }
+        execute(deps.as_mut(), mock_env(), owner.clone(), flop).unwrap();
+
+        // a hand started by the owner leaves the table to its operator
+        execute(deps.as_mut(), mock_env(), owner.clone(), start_game_msg(1, 2)).unwrap();
+        assert_eq!(load_table(deps.as_ref().storage, 1).unwrap().operator, Addr::unchecked("casino1"));
+        execute(deps.as_mut(), mock_env(), casino1.clone(), ExecuteMsg::CommunityCards { table_id: 1, game_state: GameState::Flop }).unwrap();
+
+        // the table is still casino1's once closed, casino2 can't reopen it
+        let close = ExecuteMsg::CloseTable { table_id: 1, showdown_player_ids: vec![] };
+        execute(deps.as_mut(), mock_env(), casino1.clone(), close).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), casino2, start_game_msg(1, 3));
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+        execute(deps.as_mut(), mock_env(), owner, start_game_msg(1, 3)).unwrap();
+        assert_eq!(load_table(deps.as_ref().storage, 1).unwrap().operator, Addr::unchecked("casino1"));
+        execute(deps.as_mut(), mock_env(), casino1, ExecuteMsg::CommunityCards { table_id: 1, game_state: GameState::Flop }).unwrap();
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut deps = mock_dependencies();
+        let owner = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        execute(deps.as_mut(), mock_env(), owner.clone(), start_game_msg(1, 1)).unwrap();
+        let pause = |paused, all_execution| ExecuteMsg::SetPaused { paused, reason: "dealer bug".to_string(), all_execution };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("key1", &[]), pause(true, false));
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+        // the hand in flight goes on, no new hand is dealt
+        execute(deps.as_mut(), mock_env(), owner.clone(), pause(true, false)).unwrap();
+        let paused = ContractError::Paused { reason: "dealer bug".to_string() };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), start_game_msg(2, 1));
+        assert_eq!(res.unwrap_err(), paused);
+        let flop = ExecuteMsg::CommunityCards { table_id: 1, game_state: GameState::Flop };
+        execute(deps.as_mut(), mock_env(), owner.clone(), flop).unwrap();
+
+        execute(deps.as_mut(), mock_env(), owner.clone(), pause(true, true)).unwrap();
+        let turn = ExecuteMsg::CommunityCards { table_id: 1, game_state: GameState::Turn };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), turn.clone());
+        assert_eq!(res.unwrap_err(), paused);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("key1", &coins(10, "uscrt")), ExecuteMsg::JoinTable { table_id: 1 });
+        assert_eq!(res.unwrap_err(), paused);
+        // the players still get their hands and secrets
+        assert_eq!(query_player_private_data(deps.as_ref(), 1, "key1".to_string()).unwrap().hand.len(), 2);
+        let config: ConfigResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.pause, Some(Pause { reason: "dealer bug".to_string(), all_execution: true }));
+
+        execute(deps.as_mut(), mock_env(), owner.clone(), pause(false, false)).unwrap();
+        execute(deps.as_mut(), mock_env(), owner.clone(), turn).unwrap();
+        execute(deps.as_mut(), mock_env(), owner, start_game_msg(2, 1)).unwrap();
+    }
+
+    #[test]
+    fn test_hand_history() {
+        let mut deps = mock_dependencies();
+        let owner = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        execute(deps.as_mut(), mock_env(), owner.clone(), start_game_msg(1, 1)).unwrap();
+        execute(deps.as_mut(), mock_env(), owner.clone(), ExecuteMsg::CommunityCards { table_id: 1, game_state: GameState::Flop }).unwrap();
+        let board = load_table(deps.as_ref().storage, 1).unwrap().community_cards;
+        // hand 4 is still on the table
+        for hand_ref in 2..=4 {
+            execute(deps.as_mut(), mock_env(), owner.clone(), start_game_msg(1, hand_ref)).unwrap();
+        }
+
+        let history = |deps: Deps, table_id, start_after, limit| -> HandHistoryResponse {
+            from_binary(&query(deps, mock_env(), QueryMsg::HandHistory { table_id, start_after, limit }).unwrap()).unwrap()
+        };
+        let page = history(deps.as_ref(), 1, None, Some(2));
+        assert_eq!(page.hands.iter().map(|hand| hand.hand_ref).collect::<Vec<_>>(), vec![1, 2]);
+        let first = &page.hands[0];
+        assert_eq!(first.community_cards[..3], board.flop.cards.iter().map(|card| card.to_string()).collect::<Vec<_>>()[..]);
+        assert_eq!(first.flop_retrieved_at, Some(mock_env().block.time));
+        assert_eq!(first.turn_retrieved_at, None);
+
+        let hand_refs = |page: HandHistoryResponse| page.hands.iter().map(|hand| hand.hand_ref).collect::<Vec<_>>();
+        assert_eq!(hand_refs(history(deps.as_ref(), 1, Some(2), None)), vec![3]);
+        assert_eq!(hand_refs(history(deps.as_ref(), 1, Some(0), None)), vec![1, 2, 3]);
+        assert!(history(deps.as_ref(), 1, Some(3), None).hands.is_empty());
+        assert!(history(deps.as_ref(), 2, None, None).hands.is_empty());
+    }
+
+    #[test]
+    fn test_list_tables() {
+        let mut deps = mock_dependencies();
+        let owner = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        for table_id in [3, 1, 2] {
+            execute(deps.as_mut(), mock_env(), owner.clone(), start_game_msg(table_id, 7)).unwrap();
+        }
+        let mut later = mock_env();
+        later.block.time = later.block.time.plus_seconds(60);
+        execute(deps.as_mut(), later.clone(), owner, ExecuteMsg::CommunityCards { table_id: 1, game_state: GameState::Flop }).unwrap();
+
+        let tables = |deps: Deps, start_after, limit| {
+            let res = query(deps, mock_env(), QueryMsg::Tables { start_after, limit }).unwrap();
+            assert!(!String::from_utf8(res.to_vec()).unwrap().contains("secret"));
+            from_binary::<TablesResponse>(&res).unwrap().tables
+        };
+        // in the order the tables were opened
+        let page = tables(deps.as_ref(), None, Some(2));
+        assert_eq!(
+            page,
+            vec![
+                TableInfo {
+                    table_id: 3,
+                    hand_ref: 7,
+                    player_count: 2,
+                    game_state: GameState::PreFlop,
+                    last_activity: mock_env().block.time,
+                },
+                TableInfo {
+                    table_id: 1,
+                    hand_ref: 7,
+                    player_count: 2,
+                    game_state: GameState::Flop,
+                    last_activity: later.block.time,
+                },
+            ]
         );
-        assert!(res.is_err());
+        let page = tables(deps.as_ref(), Some(1), None);
+        assert_eq!(page.iter().map(|table| table.table_id).collect::<Vec<_>>(), vec![2]);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Tables { start_after: Some(4), limit: None });
+        assert_eq!(res.unwrap_err(), StdError::generic_err("Table 4 not found"));
+    }
+
+    #[test]
+    fn test_close_table() {
+        let mut deps = mock_dependencies();
+        let owner = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        execute(deps.as_mut(), mock_env(), owner.clone(), start_game_msg(1, 1)).unwrap();
+        let hand = query_player_private_data(deps.as_ref(), 1, "key1".to_string()).unwrap().hand;
+
+        let close = ExecuteMsg::CloseTable { table_id: 1, showdown_player_ids: vec![Uuid::from_u128(1)] };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("key1", &[]), close.clone());
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+        let unknown = ExecuteMsg::CloseTable { table_id: 1, showdown_player_ids: vec![Uuid::from_u128(9)] };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), unknown);
+        assert_eq!(res.unwrap_err(), ContractError::PlayerNotFound { table_id: 1, player: Uuid::from_u128(9).to_string() });
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), close.clone()).unwrap();
+        let event = res.events.iter().find(|event| event.ty == "table_closed").unwrap();
+        let hand_log = &event.attributes.iter().find(|attr| attr.key == "hand_log").unwrap().value;
+        match serde_json_wasm::from_str(hand_log).unwrap() {
+            ResponsePayload::LastHand(log) => {
+                assert_eq!(log.hand_ref, 1);
+                assert_eq!(log.showdown_players[0].hand, hand.iter().map(|card| card.to_string()).collect::<Vec<_>>());
+            }
+            _ => panic!("Expected LastHand response"),
+        }
+        assert!(load_table(deps.as_ref().storage, 1).is_none());
+        let history: HandHistoryResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::HandHistory { table_id: 1, start_after: None, limit: None }).unwrap())
+                .unwrap();
+        assert_eq!(history.hands.len(), 1);
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), close);
+        assert_eq!(res.unwrap_err(), ContractError::TableNotFound { table_id: 1 });
+
+        // the table reopens after its archived hands, and the next hands are archived next to them
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), start_game_msg(1, 1));
+        assert_eq!(res.unwrap_err(), ContractError::HandRefNotIncreasing { table_id: 1, hand_ref: 1, previous: 1 });
+        execute(deps.as_mut(), mock_env(), owner.clone(), start_game_msg(1, 2)).unwrap();
+        execute(deps.as_mut(), mock_env(), owner.clone(), start_game_msg(1, 3)).unwrap();
+        let history: HandHistoryResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::HandHistory { table_id: 1, start_after: None, limit: None }).unwrap())
+                .unwrap();
+        assert_eq!(history.hands.iter().map(|hand| hand.hand_ref).collect::<Vec<_>>(), vec![1, 2]);
+
+        // the escrowed chips of a hand in progress stay on the table
+        for player in ["key1", "key2"] {
+            execute(deps.as_mut(), mock_env(), mock_info(player, &coins(100, "uscrt")), ExecuteMsg::JoinTable { table_id: 2 }).unwrap();
+        }
+        start_betting_hand(deps.as_mut(), 2, &[100, 100], 0, Some(Currency::Native)).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::CloseTable { table_id: 2, showdown_player_ids: vec![] });
+        assert_eq!(res.unwrap_err(), ContractError::HandInProgress { table_id: 2, hand_ref: 1 });
+    }
+
+    #[test]
+    fn test_prune_tables() {
+        let mut deps = mock_dependencies();
+        let owner = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        for table_id in [1, 2] {
+            execute(deps.as_mut(), mock_env(), owner.clone(), start_game_msg(table_id, 1)).unwrap();
+        }
+        for player in ["key1", "key2"] {
+            execute(deps.as_mut(), mock_env(), mock_info(player, &coins(100, "uscrt")), ExecuteMsg::JoinTable { table_id: 3 }).unwrap();
+        }
+        start_betting_hand(deps.as_mut(), 3, &[100, 100], 0, Some(Currency::Native)).unwrap();
+        execute(deps.as_mut(), mock_env(), owner.clone(), start_game_msg(4, 1)).unwrap();
+        let mut later = mock_env();
+        later.block.time = later.block.time.plus_seconds(100);
+        execute(deps.as_mut(), later, owner.clone(), ExecuteMsg::CommunityCards { table_id: 2, game_state: GameState::Flop }).unwrap();
+
+        let prune = |start_after, limit| ExecuteMsg::PruneTables {
+            older_than: mock_env().block.time.plus_seconds(50),
+            start_after,
+            limit,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("key1", &[]), prune(None, None));
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+        let attributes = |res: Response| res.attributes.into_iter().map(|attr| attr.value).collect::<Vec<_>>();
+
+        // table 4 takes the place of table 1 in the index and is looked at next
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), prune(None, Some(2))).unwrap();
+        assert_eq!(attributes(res), vec!["[1,4]", "false"]);
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), prune(None, Some(1))).unwrap();
+        assert_eq!(attributes(res), vec!["[]", "false", "3"]);
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), prune(Some(3), None)).unwrap();
+        assert_eq!(attributes(res), vec!["[]", "true"]);
+        let res = execute(deps.as_mut(), mock_env(), owner, prune(Some(1), None));
+        assert_eq!(res.unwrap_err(), ContractError::TableNotFound { table_id: 1 });
+
+        assert!(load_table(deps.as_ref().storage, 1).is_none());
+        assert!(load_table(deps.as_ref().storage, 4).is_none());
+        let tables: TablesResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Tables { start_after: None, limit: None }).unwrap()).unwrap();
+        assert_eq!(tables.tables.iter().map(|table| table.table_id).collect::<Vec<_>>(), vec![3, 2]);
+    }
+
+    #[test]
+    fn test_hand_ref_replay() {
+        let mut deps = mock_dependencies();
+        let owner = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        for player in ["key1", "key2"] {
+            execute(deps.as_mut(), mock_env(), mock_info(player, &coins(100, "uscrt")), ExecuteMsg::JoinTable { table_id: 1 }).unwrap();
+        }
+        start_betting_hand(deps.as_mut(), 1, &[100, 100], 0, Some(Currency::Native)).unwrap();
+        act(deps.as_mut(), "key1", ExecuteMsg::Call { table_id: 1 }).unwrap();
+
+        for hand_ref in [0, 1] {
+            let res = execute(deps.as_mut(), mock_env(), owner.clone(), start_game_msg(1, hand_ref));
+            assert_eq!(res.unwrap_err(), ContractError::HandRefNotIncreasing { table_id: 1, hand_ref, previous: 1 });
+        }
+        let next_hand = |force| -> ExecuteMsg {
+            NewHand {
+                hand_ref: 2,
+                force,
+                ..NewHand::default()
+            }
+            .into()
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), next_hand(false));
+        assert_eq!(
+            res.unwrap_err(),
+            ContractError::PreviousHandInProgress { table_id: 1, hand_ref: 1, game_state: GameState::PreFlop }
+        );
+
+        // the aborted hand gives the blinds back
+        execute(deps.as_mut(), mock_env(), owner.clone(), next_hand(true)).unwrap();
+        for player in ["key1", "key2"] {
+            assert_eq!(load_balance(deps.as_ref().storage, 1, &Currency::Native, player), Uint128::new(100));
+        }
+        let finish = ExecuteMsg::Showdown {
+            table_id: 1,
+            game_state: GameState::PreFlop,
+            showdown_player_ids: vec![],
+            contributions: vec![],
+            pot: None,
+        };
+        execute(deps.as_mut(), mock_env(), owner.clone(), finish).unwrap();
+        execute(deps.as_mut(), mock_env(), owner, NewHand { hand_ref: 3, ..NewHand::default() }.into()).unwrap();
+    }
+
+    #[test]
+    fn test_void_hand() {
+        let mut deps = mock_dependencies();
+        let owner = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        for player in ["key1", "key2"] {
+            execute(deps.as_mut(), mock_env(), mock_info(player, &coins(100, "uscrt")), ExecuteMsg::JoinTable { table_id: 1 }).unwrap();
+        }
+        start_betting_hand(deps.as_mut(), 1, &[100, 100], 0, Some(Currency::Native)).unwrap();
+        act(deps.as_mut(), "key1", ExecuteMsg::Raise { table_id: 1, amount: Uint128::new(30) }).unwrap();
+        let table = load_table(deps.as_ref().storage, 1).unwrap();
+
+        let void = ExecuteMsg::VoidHand { table_id: 1, reason: "misdeal".to_string() };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("key1", &[]), void.clone());
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), void.clone()).unwrap();
+        let event = res.events.iter().find(|event| event.ty == "hand_voided").unwrap();
+        assert!(event.attributes.iter().any(|attr| attr.key == "reason" && attr.value == "misdeal"));
+        let hand_log = &event.attributes.iter().find(|attr| attr.key == "hand_log").unwrap().value;
+        match serde_json_wasm::from_str(hand_log).unwrap() {
+            ResponsePayload::LastHand(log) => {
+                let hands: Vec<Vec<String>> = table
+                    .players
+                    .iter()
+                    .map(|player| player.hand.iter().map(|card| card.to_string()).collect())
+                    .collect();
+                assert_eq!(log.showdown_players.into_iter().map(|player| player.hand).collect::<Vec<_>>(), hands);
+                assert_eq!(log.deck, Binary::from(table.deck.to_bytes()));
+                assert_eq!(log.deck_salt, table.deck_salt);
+                assert_eq!(log.void_reason, Some("misdeal".to_string()));
+            }
+            _ => panic!("Expected LastHand response"),
+        }
+        for player in ["key1", "key2"] {
+            assert_eq!(load_balance(deps.as_ref().storage, 1, &Currency::Native, player), Uint128::new(100));
+        }
+
+        let voided = ContractError::HandVoided { table_id: 1, hand_ref: 1 };
+        let showdown = ExecuteMsg::Showdown {
+            table_id: 1,
+            game_state: GameState::PreFlop,
+            showdown_player_ids: vec![],
+            contributions: vec![],
+            pot: None,
+        };
+        for msg in [ExecuteMsg::CommunityCards { table_id: 1, game_state: GameState::Flop }, showdown, void] {
+            assert_eq!(execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err(), voided);
+        }
+        assert_eq!(act(deps.as_mut(), "key2", ExecuteMsg::Call { table_id: 1 }).unwrap_err(), voided);
+
+        // the next hand starts without forcing, the archived log keeps the reason
+        execute(deps.as_mut(), mock_env(), owner.clone(), NewHand { hand_ref: 2, ..NewHand::default() }.into()).unwrap();
+        let history: HandHistoryResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::HandHistory { table_id: 1, start_after: None, limit: None }).unwrap())
+                .unwrap();
+        assert_eq!(history.hands[0].void_reason, Some("misdeal".to_string()));
+
+        // a finished hand can't be voided
+        let showdown = ExecuteMsg::Showdown {
+            table_id: 1,
+            game_state: GameState::PreFlop,
+            showdown_player_ids: vec![Uuid::from_u128(1), Uuid::from_u128(2)],
+            contributions: vec![],
+            pot: None,
+        };
+        execute(deps.as_mut(), mock_env(), owner.clone(), showdown).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::VoidHand { table_id: 1, reason: "late".to_string() });
+        assert_eq!(res.unwrap_err(), ContractError::HandFinished { table_id: 1, hand_ref: 2 });
+    }
+
+    #[test]
+    fn test_transfer_ownership() {
+        let mut deps = mock_dependencies();
+        let owner = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), owner.clone(), InstantiateMsg { accepted_token: None, rake: None }).unwrap();
+        let new_owner = mock_info("new_owner", &[]);
+        let res = execute(deps.as_mut(), mock_env(), new_owner.clone(), ExecuteMsg::AcceptOwnership {});
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+        let transfer = ExecuteMsg::TransferOwnership { new_owner: "new_owner".to_string() };
+        execute(deps.as_mut(), mock_env(), owner.clone(), transfer).unwrap();
+        let config: ConfigResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.owner, Addr::unchecked("creator"));
+        assert_eq!(config.pending_owner, Some(Addr::unchecked("new_owner")));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("someone", &[]), ExecuteMsg::AcceptOwnership {});
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+        execute(deps.as_mut(), mock_env(), new_owner.clone(), ExecuteMsg::AcceptOwnership {}).unwrap();
+        let config: ConfigResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!((config.owner, config.pending_owner), (Addr::unchecked("new_owner"), None));
+        let res = execute(deps.as_mut(), mock_env(), owner, ExecuteMsg::SetRake { rake: None });
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+        execute(deps.as_mut(), mock_env(), new_owner, ExecuteMsg::SetRake { rake: None }).unwrap();
+    }
+
+    fn player_shares(players: &[&PlayerDataResponse], game_state: GameState) -> Vec<SecretShare> {
+        players
+            .iter()
+            .map(|player| SecretShare {
+                index: player.share_index,
+                share: match game_state {
+                    GameState::Flop => &player.flop_secret_share,
+                    GameState::Turn => &player.turn_secret_share,
+                    _ => &player.river_secret_share,
+                }
+                .parse()
+                .unwrap(),
+            })
+            .collect()
     }
     
     #[test]
@@ -805,7 +4399,7 @@ mod complete_tests {
         let mut deps = mock_dependencies();
         
         
-        let msg = InstantiateMsg {};
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
         let info = mock_info("creator", &coins(1000, "earth"));
         let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         
@@ -815,11 +4409,13 @@ mod complete_tests {
                 username: "player1".to_string(),
                 player_id: Uuid::parse_str("2928c53b-5d14-4a7c-b56e-83ef56a0644e").unwrap(),
                 public_key: "key1".to_string(),
+                stack: Uint128::zero(),
             },
             StartGamePlayer {
                 username: "player2".to_string(),
                 player_id: Uuid::parse_str("8f204fcc-54a5-4473-8ac3-4845bff291ab").unwrap(),
                 public_key: "key2".to_string(),
+                stack: Uint128::zero(),
             },
         ];
         
@@ -827,12 +4423,11 @@ mod complete_tests {
             deps.as_mut(),
             mock_env(),
             info.clone(),
-            ExecuteMsg::StartGame {
-                table_id: 1,
-                hand_ref: 1,
-                players,
-                prev_hand_showdown_players: vec![],
-            },
+            NewHand {
+                players,
+                ..NewHand::default()
+            }
+            .into(),
         )
         .unwrap();
         
@@ -884,7 +4479,7 @@ mod complete_tests {
     fn test_invalid_game_state() {
         let mut deps = mock_dependencies();
         
-        let msg = InstantiateMsg {};
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
         let info = mock_info("creator", &coins(1000, "earth"));
         let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         
@@ -893,11 +4488,13 @@ mod complete_tests {
                 username: "player1".to_string(),
                 player_id: Uuid::parse_str("2928c53b-5d14-4a7c-b56e-83ef56a0644e").unwrap(),
                 public_key: "key1".to_string(),
+                stack: Uint128::zero(),
             },
             StartGamePlayer {
                 username: "player2".to_string(),
                 player_id: Uuid::parse_str("8f204fcc-54a5-4473-8ac3-4845bff291ab").unwrap(),
                 public_key: "key2".to_string(),
+                stack: Uint128::zero(),
             },
         ];
         
@@ -905,12 +4502,11 @@ mod complete_tests {
             deps.as_mut(),
             mock_env(),
             info.clone(),
-            ExecuteMsg::StartGame {
-                table_id: 1,
-                hand_ref: 1,
+            NewHand {
                 players,
-                prev_hand_showdown_players: vec![],
-            },
+                ..NewHand::default()
+            }
+            .into(),
         )
         .unwrap();
         
@@ -927,9 +4523,10 @@ mod complete_tests {
         
         assert!(res.is_err());
         match res.unwrap_err() {
-            ContractError::GameStateError { method, table_id, game_state } => {
+            ContractError::GameStateError { method, table_id, expected, game_state } => {
                 assert_eq!(method, "distribute_community_cards");
                 assert_eq!(table_id, 1);
+                assert_eq!(expected, Some(GameState::Flop));
                 assert_eq!(game_state, Some(GameState::PreFlop));
             },
             _ => panic!("Expected GameStateError"),
@@ -941,7 +4538,7 @@ mod complete_tests {
         let mut deps = mock_dependencies();
         
         
-        let msg = InstantiateMsg {};
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
         let info = mock_info("creator", &coins(1000, "earth"));
         let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         
@@ -954,11 +4551,13 @@ mod complete_tests {
                 username: "player1".to_string(),
                 player_id: player1_id,
                 public_key: "key1".to_string(),
+                stack: Uint128::zero(),
             },
             StartGamePlayer {
                 username: "player2".to_string(),
                 player_id: player2_id,
                 public_key: "key2".to_string(),
+                stack: Uint128::zero(),
             },
         ];
         
@@ -966,15 +4565,26 @@ mod complete_tests {
             deps.as_mut(),
             mock_env(),
             info.clone(),
-            ExecuteMsg::StartGame {
-                table_id: 1,
-                hand_ref: 1,
+            NewHand {
                 players,
-                prev_hand_showdown_players: vec![],
-            },
+                ..NewHand::default()
+            }
+            .into(),
         )
         .unwrap();
         
+        for game_state in [GameState::Flop, GameState::Turn, GameState::River] {
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::CommunityCards {
+                    table_id: 1,
+                    game_state,
+                },
+            )
+            .unwrap();
+        }
         
         let res = execute(
             deps.as_mut(),
@@ -984,6 +4594,8 @@ mod complete_tests {
                 table_id: 1,
                 game_state: GameState::River,
                 showdown_player_ids: vec![player1_id, player2_id],
+                contributions: vec![],
+                pot: None,
             },
         )
         .unwrap();
@@ -999,7 +4611,7 @@ mod complete_tests {
         let mut deps = mock_dependencies();
         
         
-        let msg = InstantiateMsg {};
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
         let info = mock_info("creator", &coins(1000, "earth"));
         let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         
@@ -1012,11 +4624,13 @@ mod complete_tests {
                 username: "player1".to_string(),
                 player_id: player1_id,
                 public_key: "key1".to_string(),
+                stack: Uint128::zero(),
             },
             StartGamePlayer {
                 username: "player2".to_string(),
                 player_id: player2_id,
                 public_key: "key2".to_string(),
+                stack: Uint128::zero(),
             },
         ];
         
@@ -1024,12 +4638,11 @@ mod complete_tests {
             deps.as_mut(),
             mock_env(),
             info.clone(),
-            ExecuteMsg::StartGame {
-                table_id: 1,
-                hand_ref: 1,
+            NewHand {
                 players,
-                prev_hand_showdown_players: vec![],
-            },
+                ..NewHand::default()
+            }
+            .into(),
         )
         .unwrap();
         
@@ -1041,8 +4654,10 @@ mod complete_tests {
             info.clone(),
             ExecuteMsg::Showdown {
                 table_id: 1,
-                game_state: GameState::River,
+                game_state: GameState::PreFlop,
                 showdown_player_ids: vec![non_existent_player],
+                contributions: vec![],
+                pot: None,
             },
         );
         
@@ -1056,6 +4671,390 @@ mod complete_tests {
         }
     }
 
+    #[test]
+    fn test_out_of_order_reveal() {
+        let mut deps = mock_dependencies();
+        
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        
+        let players = vec![
+            StartGamePlayer {
+                username: "player1".to_string(),
+                player_id: Uuid::parse_str("2928c53b-5d14-4a7c-b56e-83ef56a0644e").unwrap(),
+                public_key: "key1".to_string(),
+                stack: Uint128::zero(),
+            },
+            StartGamePlayer {
+                username: "player2".to_string(),
+                player_id: Uuid::parse_str("8f204fcc-54a5-4473-8ac3-4845bff291ab").unwrap(),
+                public_key: "key2".to_string(),
+                stack: Uint128::zero(),
+            },
+        ];
+        
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                players,
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::CommunityCards {
+                table_id: 1,
+                game_state: GameState::River,
+            },
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            ContractError::GameStateError {
+                method: "distribute_community_cards".to_string(),
+                table_id: 1,
+                expected: Some(GameState::Flop),
+                game_state: Some(GameState::River),
+            }
+        );
+        
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::CommunityCards {
+                table_id: 1,
+                game_state: GameState::Flop,
+            },
+        )
+        .unwrap();
+        
+        // the flop cannot be revealed twice
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::CommunityCards {
+                table_id: 1,
+                game_state: GameState::Flop,
+            },
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            ContractError::GameStateError {
+                method: "distribute_community_cards".to_string(),
+                table_id: 1,
+                expected: Some(GameState::Turn),
+                game_state: Some(GameState::Flop),
+            }
+        );
+        
+        // all-in showdown must be called from the street the table is on
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Showdown {
+                table_id: 1,
+                game_state: GameState::PreFlop,
+                showdown_player_ids: vec![],
+                contributions: vec![],
+                pot: None,
+            },
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            ContractError::GameStateError {
+                method: "showdown".to_string(),
+                table_id: 1,
+                expected: Some(GameState::Flop),
+                game_state: Some(GameState::PreFlop),
+            }
+        );
+    }
+    
+    #[test]
+    fn test_showdown_twice() {
+        let mut deps = mock_dependencies();
+        
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        
+        let player1_id = Uuid::parse_str("2928c53b-5d14-4a7c-b56e-83ef56a0644e").unwrap();
+        let player2_id = Uuid::parse_str("8f204fcc-54a5-4473-8ac3-4845bff291ab").unwrap();
+        
+        let players = vec![
+            StartGamePlayer {
+                username: "player1".to_string(),
+                player_id: player1_id,
+                public_key: "key1".to_string(),
+                stack: Uint128::zero(),
+            },
+            StartGamePlayer {
+                username: "player2".to_string(),
+                player_id: player2_id,
+                public_key: "key2".to_string(),
+                stack: Uint128::zero(),
+            },
+        ];
+        
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                players,
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        
+        // the hand is still preflop
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Showdown {
+                table_id: 1,
+                game_state: GameState::Flop,
+                showdown_player_ids: vec![player1_id, player2_id],
+                contributions: vec![],
+                pot: None,
+            },
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            ContractError::GameStateError {
+                method: "showdown".to_string(),
+                table_id: 1,
+                expected: Some(GameState::PreFlop),
+                game_state: Some(GameState::Flop),
+            }
+        );
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Showdown {
+                table_id: 1,
+                game_state: GameState::PreFlop,
+                showdown_player_ids: vec![player1_id, player2_id],
+                contributions: vec![],
+                pot: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(load_table(deps.as_ref().storage, 1).unwrap().game_state, GameState::Closed);
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Showdown {
+                table_id: 1,
+                game_state: GameState::PreFlop,
+                showdown_player_ids: vec![player1_id, player2_id],
+                contributions: vec![],
+                pot: None,
+            },
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            ContractError::GameStateError {
+                method: "showdown".to_string(),
+                table_id: 1,
+                expected: None,
+                game_state: Some(GameState::PreFlop),
+            }
+        );
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::CommunityCards {
+                table_id: 1,
+                game_state: GameState::Flop,
+            },
+        );
+        assert!(matches!(res.unwrap_err(), ContractError::GameStateError { .. }));
+    }
+
+    #[test]
+    fn test_deck_commitment() {
+        let mut deps = mock_dependencies();
+        
+        let msg = InstantiateMsg { accepted_token: None, rake: None };
+        let info = mock_info("creator", &coins(1000, "earth"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        
+        let player1_id = Uuid::parse_str("2928c53b-5d14-4a7c-b56e-83ef56a0644e").unwrap();
+        let player2_id = Uuid::parse_str("8f204fcc-54a5-4473-8ac3-4845bff291ab").unwrap();
+        let players = vec![
+            StartGamePlayer {
+                username: "player1".to_string(),
+                player_id: player1_id,
+                public_key: "key1".to_string(),
+                stack: Uint128::zero(),
+            },
+            StartGamePlayer {
+                username: "player2".to_string(),
+                player_id: player2_id,
+                public_key: "key2".to_string(),
+                stack: Uint128::zero(),
+            },
+        ];
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                players: players.clone(),
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        
+        let response_attr = res.attributes.iter().find(|attr| attr.key == "response").unwrap();
+        let commitment = match serde_json_wasm::from_str(&response_attr.value).unwrap() {
+            ResponsePayload::StartGame(start_game) => start_game.deck_commitment,
+            _ => panic!("Expected StartGame response"),
+        };
+        let player_info1 = query_player_private_data(deps.as_ref(), 1, "key1".to_string()).unwrap();
+        let player_info2 = query_player_private_data(deps.as_ref(), 1, "key2".to_string()).unwrap();
+        
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            NewHand {
+                hand_ref: 2,
+                players,
+                prev_hand_showdown_players: vec![player1_id],
+                force: true, // the first hand was never played out
+                ..NewHand::default()
+            }
+            .into(),
+        )
+        .unwrap();
+        
+        let log_attr = res.attributes.iter().find(|attr| attr.key == "previous_hand_log").unwrap();
+        let log = match serde_json_wasm::from_str(&log_attr.value).unwrap() {
+            ResponsePayload::LastHand(log) => log,
+            _ => panic!("Expected LastHand response"),
+        };
+        
+        let mut hasher = Sha256::new();
+        hasher.update(log.deck_salt.as_slice());
+        hasher.update(log.deck.as_slice());
+        assert_eq!(hasher.finalize().to_vec(), commitment.to_vec());
+        
+        let deck = Deck::from_bytes(log.deck.as_slice());
+        assert_eq!(deck.cards.len(), 52);
+        assert_eq!(player_info1.hand, vec![deck.cards[51].clone(), deck.cards[50].clone()]);
+        assert_eq!(player_info2.hand, vec![deck.cards[49].clone(), deck.cards[48].clone()]);
+        let board: Vec<String> = deck.cards[43..48].iter().rev().map(|card| card.to_string()).collect();
+        assert_eq!(log.community_cards, board);
+    }
+
+    fn test_seed(i: u64) -> [u8; SECRET_LENGTH] {
+        let mut seed = [0u8; SECRET_LENGTH];
+        seed[..8].copy_from_slice(&i.to_le_bytes());
+        seed
+    }
+
+    fn chi_square(counts: &[u64], expected: f64) -> f64 {
+        counts
+            .iter()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum()
+    }
+
+    #[test]
+    fn test_shuffle_is_permutation() {
+        for i in 0..100 {
+            let mut deck = Deck::new();
+            helpers::shuffle_deck(&mut deck, &test_seed(i));
+            let mut bytes = deck.to_bytes();
+            bytes.sort();
+            let mut expected = Deck::new().to_bytes();
+            expected.sort();
+            assert_eq!(bytes, expected);
+        }
+    }
+
+    #[test]
+    fn test_shuffle_depends_on_whole_seed() {
+        let mut deck1 = Deck::new();
+        let mut deck2 = Deck::new();
+        let mut seed = test_seed(1);
+        helpers::shuffle_deck(&mut deck1, &seed);
+        seed[SECRET_LENGTH - 1] = 1;
+        helpers::shuffle_deck(&mut deck2, &seed);
+        assert_ne!(deck1, deck2);
+    }
+
+    #[test]
+    fn test_uniform_index_distribution() {
+        // 52 does not divide 2^32, so a plain modulo would be biased here
+        let mut rng = ContractPrng::new(&test_seed(7), &[]);
+        let bound = 52u32;
+        let draws = 520_000u64;
+        let mut counts = vec![0u64; bound as usize];
+        for _ in 0..draws {
+            counts[helpers::uniform_index(&mut rng, bound) as usize] += 1;
+        }
+        // 51 degrees of freedom, critical value at p = 0.001 is 87.97
+        assert!(chi_square(&counts, (draws / bound as u64) as f64) < 87.97);
+    }
+
+    #[test]
+    fn test_shuffle_permutations_uniform() {
+        // every ordering of a 4-card deck must come out equally often across seeds
+        let mut deck = Deck::new();
+        deck.cards.truncate(4);
+        let shuffles = 24_000u64;
+        let mut counts = std::collections::HashMap::new();
+        for i in 0..shuffles {
+            let mut shuffled = deck.clone();
+            helpers::shuffle_deck(&mut shuffled, &test_seed(i));
+            *counts.entry(shuffled.to_bytes()).or_insert(0u64) += 1;
+        }
+        assert_eq!(counts.len(), 24);
+        let counts: Vec<u64> = counts.into_values().collect();
+        // 23 degrees of freedom, critical value at p = 0.001 is 49.73
+        assert!(chi_square(&counts, (shuffles / 24) as f64) < 49.73);
+    }
+
+    #[test]
+    fn test_shuffle_positions_uniform() {
+        // each card must land in each position of the full deck equally often across seeds
+        let shuffles = 26_000u64;
+        let mut counts = vec![0u64; 52 * 52];
+        let cards = Deck::new().to_bytes();
+        for i in 0..shuffles {
+            let mut deck = Deck::new();
+            helpers::shuffle_deck(&mut deck, &test_seed(i));
+            for (position, card) in deck.to_bytes().iter().enumerate() {
+                let card_index = cards.iter().position(|c| c == card).unwrap();
+                counts[card_index * 52 + position] += 1;
+            }
+        }
+        // 51 * 51 = 2601 degrees of freedom, critical value at p = 0.001 is about 2831
+        assert!(chi_square(&counts, (shuffles / 52) as f64) < 2831.0);
+    }
+
     pub fn addition_shares(shares: Vec<u64>) -> u64 {
         shares.iter().copied().fold(0u64, u64::wrapping_add)
     }
@@ -1064,7 +5063,7 @@ mod complete_tests {
     fn test_additive_sharing() {
         let secret = 14151497078262209000u64;
     let mut counter = 0;
-    let shares = helpers::additive_secret_sharing(&mock_env(), 2, secret, &mut counter).unwrap();
+    helpers::additive_secret_sharing(&mock_env(), 2, secret, &mut counter).unwrap();
     let shares = [8676118583430535000, 5475378494831674000, ];
          let sum = shares.iter().copied().fold(0u64, u64::wrapping_add);
          println!("{:?}", sum);
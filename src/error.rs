@@ -1,7 +1,7 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use thiserror::Error;
 
-use crate::state::GameState;
+use crate::state::{GameState, PlayerAction};
 
 #[derive(Error, Debug, PartialEq)]
 pub enum ContractError {
@@ -10,14 +10,23 @@ pub enum ContractError {
     Std(#[from] StdError),
 
     #[error("Unauthorized")]
-    // issued when message sender != owner
+    // issued when the message sender doesn't have the role the message requires
     Unauthorized {},
 
-    #[error("Game state error in method {method} for table {table_id}: got {game_state:?}")]
-    // issued when game state is invalid
+    #[error("{operator} is already an operator")]
+    // issued when adding an operator twice
+    OperatorExists { operator: String },
+
+    #[error("{operator} is not an operator")]
+    // issued when removing an unknown operator
+    OperatorNotFound { operator: String },
+
+    #[error("Game state error in method {method} for table {table_id}: expected {expected:?}, got {game_state:?}")]
+    // issued when game state is invalid or requested out of order
     GameStateError {
         method: String,
         table_id: u32,
+        expected: Option<GameState>,
         game_state: Option<GameState>,
     },
 
@@ -25,6 +34,10 @@ pub enum ContractError {
     // issued when player is not found
     PlayerNotFound { table_id: u32, player: String },
 
+    #[error("Player {player} does not make a five card hand in table {table_id}")]
+    // issued when a shown hand and the board can't be evaluated, e.g. the hand was never dealt
+    InvalidHand { table_id: u32, player: String },
+
     #[error("Table {table_id} not found")]
     // issued when table is not found
     TableNotFound { table_id: u32 },
@@ -45,4 +58,108 @@ pub enum ContractError {
     #[error("Players invalide count: {count}")]
     // issued when player count is invalid
     InvalidPlayerCount { count: usize },
-}
\ No newline at end of file
+
+    #[error("Invalid {game_state:?} secret share from player {player} in table {table_id}")]
+    // issued when a submitted share does not match the share dealt to the player
+    InvalidSecretShare { table_id: u32, player: String, game_state: GameState },
+
+    #[error("Player {player} already submitted a {game_state:?} share in table {table_id}")]
+    // issued when a player submits the same phase share twice
+    DuplicateSecretShare { table_id: u32, player: String, game_state: GameState },
+
+    #[error("Cannot run it {runs} times in table {table_id}")]
+    // issued when runs is below 2 or the rest of the deck is too small for every board
+    InvalidRunCount { table_id: u32, runs: u32 },
+
+    #[error("Invalid secret sharing threshold {threshold} for {players} players")]
+    // issued when the threshold is outside 2..=players
+    InvalidThreshold { threshold: u32, players: usize },
+
+    #[error("Betting is not enabled in table {table_id}")]
+    // issued when a betting action is sent to a table without on-chain betting
+    BettingDisabled { table_id: u32 },
+
+    #[error("Invalid blinds {small_blind}/{big_blind}")]
+    // issued when a blind is zero or the small blind is above the big blind
+    InvalidBlinds { small_blind: Uint128, big_blind: Uint128 },
+
+    #[error("Player {player} has no chips")]
+    // issued when a player joins a betting hand with an empty stack
+    EmptyStack { player: String },
+
+    #[error("It is not player {player}'s turn in table {table_id}")]
+    // issued when a player acts out of turn or after the betting round is over
+    NotPlayerTurn { table_id: u32, player: String },
+
+    #[error("Player {player} cannot {action:?} in table {table_id}")]
+    // issued when the action is not allowed facing the current bet
+    InvalidAction { table_id: u32, player: String, action: PlayerAction },
+
+    #[error("Invalid amount {amount} in table {table_id}: expected between {min} and {max}")]
+    // issued when a bet or raise is below the minimum or above the player's stack
+    InvalidBetAmount { table_id: u32, amount: Uint128, min: Uint128, max: Uint128 },
+
+    #[error("Betting round still open on {game_state:?} in table {table_id}")]
+    // issued when the hand is moved forward before the players are done betting
+    BettingRoundOpen { table_id: u32, game_state: GameState },
+
+    #[error("Token {token} is not accepted")]
+    // issued when a Receive callback comes from another contract than the accepted token
+    UnsupportedToken { token: String },
+
+    #[error("No token is accepted for the escrow")]
+    // issued when an escrow hand starts before the accepted token is configured
+    NoAcceptedToken {},
+
+    #[error("The accepted token can't change while the contract holds {amount} of it")]
+    // issued when the accepted token is replaced before every balance, chip in play and the treasury is paid out
+    TokenHeld { amount: Uint128 },
+
+    #[error("Invalid receive message")]
+    // issued when a Receive callback has no ReceiveMsg attached
+    InvalidReceiveMsg {},
+
+    #[error("Player {player} only has {balance} in escrow in table {table_id}")]
+    // issued when a player brings more chips than escrowed or withdraws an empty balance
+    InsufficientBalance { table_id: u32, player: String, balance: Uint128 },
+
+    #[error("Invalid funds, only {denom} is accepted")]
+    // issued when JoinTable comes without coins or with another denomination
+    InvalidFunds { denom: String },
+
+    #[error("Player {player} is still playing a hand in table {table_id}")]
+    // issued when a player leaves a table before the end of the hand
+    PlayerInHand { table_id: u32, player: String },
+
+    #[error("Hand {hand_ref} of table {table_id} doesn't follow hand {previous}")]
+    // issued when a new hand_ref isn't above the previous one of the table
+    HandRefNotIncreasing { table_id: u32, hand_ref: u32, previous: u32 },
+
+    #[error("Hand {hand_ref} of table {table_id} hasn't reached the showdown, {game_state:?}")]
+    // issued when starting a hand over an unfinished one without forcing it
+    PreviousHandInProgress { table_id: u32, hand_ref: u32, game_state: GameState },
+
+    #[error("Hand {hand_ref} of table {table_id} was voided")]
+    // issued when dealing or playing a voided hand
+    HandVoided { table_id: u32, hand_ref: u32 },
+
+    #[error("Hand {hand_ref} of table {table_id} is already finished")]
+    // issued when voiding a hand that reached its showdown
+    HandFinished { table_id: u32, hand_ref: u32 },
+
+    #[error("Hand {hand_ref} of table {table_id} still holds escrowed chips")]
+    // issued when closing a table in the middle of an escrow hand
+    HandInProgress { table_id: u32, hand_ref: u32 },
+
+    #[error("Invalid rake of {basis_points} basis points")]
+    // issued when the rake is above 100%
+    InvalidRake { basis_points: u16 },
+
+    #[error("No rake to withdraw")]
+    // issued when the treasury is empty
+    EmptyTreasury {},
+
+    #[error("Contract paused: {reason}")]
+    // issued when the owner paused the message
+    Paused { reason: String },
+}
@@ -0,0 +1,454 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::state::{Card, DeckType};
+
+/*
+* Poker hand ranking on top of state::Card, where rank 1 is the Ace.
+* Ranks are compared as values 2..=14 (the Ace plays high, except in the wheel: the 5-high straight,
+* or the 9-high A-6-7-8-9 straight of the short deck).
+*/
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum HandCategory {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfAKind,
+    Straight,
+    Flush,
+    FullHouse,
+    FourOfAKind,
+    StraightFlush,
+}
+
+/*
+ * Ordered by category first, then by the kickers: the values that break ties inside the category,
+ * most significant first (e.g. [trips, pair] for a full house, [high card] for a straight).
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HandRank {
+    pub category: HandCategory,
+    pub kickers: Vec<u8>,
+}
+
+pub fn value(card: &Card) -> u8 {
+    if card.rank() == 1 {
+        14
+    } else {
+        card.rank()
+    }
+}
+
+/*
+ * The comparison key of a hand on a deck: the short deck has fewer cards of each suit, so a flush is
+ * rarer than a full house and beats it. HandRank's own ordering is the standard deck's.
+ */
+pub fn strength<'a>(rank: &'a HandRank, deck_type: &DeckType) -> (u8, &'a [u8]) {
+    let category = match (deck_type, rank.category) {
+        (DeckType::ShortDeck, HandCategory::Flush) => HandCategory::FullHouse,
+        (DeckType::ShortDeck, HandCategory::FullHouse) => HandCategory::Flush,
+        (_, category) => category,
+    };
+    (category as u8, &rank.kickers)
+}
+
+/*
+ * None unless there are exactly five cards.
+ */
+pub fn evaluate_five(cards: &[Card], deck_type: &DeckType) -> Option<HandRank> {
+    if cards.len() != 5 {
+        return None;
+    }
+
+    let mut values: Vec<u8> = cards.iter().map(value).collect();
+    values.sort_unstable_by(|a, b| b.cmp(a));
+
+    let flush = cards.iter().all(|card| card.suit() == cards[0].suit());
+    let distinct = values.windows(2).all(|pair| pair[0] != pair[1]);
+    let wheel = match deck_type {
+        DeckType::Standard => [14, 5, 4, 3, 2],
+        DeckType::ShortDeck => [14, 9, 8, 7, 6],
+    };
+    let straight_high = if distinct && values[0] - values[4] == 4 {
+        Some(values[0])
+    } else if values == wheel {
+        Some(wheel[1])
+    } else {
+        None
+    };
+
+    if let Some(high) = straight_high {
+        let category = if flush {
+            HandCategory::StraightFlush
+        } else {
+            HandCategory::Straight
+        };
+        return Some(HandRank {
+            category,
+            kickers: vec![high],
+        });
+    }
+
+    // (count, value) groups, biggest groups first then highest values
+    let mut groups: Vec<(u8, u8)> = Vec::new();
+    for value in values.iter() {
+        match groups.iter_mut().find(|(_, v)| v == value) {
+            Some(group) => group.0 += 1,
+            None => groups.push((1, *value)),
+        }
+    }
+    groups.sort_unstable_by(|a, b| b.cmp(a));
+
+    let category = match (groups[0].0, groups.get(1).map(|group| group.0)) {
+        (4, _) => HandCategory::FourOfAKind,
+        (3, Some(2)) => HandCategory::FullHouse,
+        _ if flush => HandCategory::Flush,
+        (3, _) => HandCategory::ThreeOfAKind,
+        (2, Some(2)) => HandCategory::TwoPair,
+        (2, _) => HandCategory::OnePair,
+        _ => HandCategory::HighCard,
+    };
+
+    Some(HandRank {
+        category,
+        kickers: groups.iter().map(|(_, value)| *value).collect(),
+    })
+}
+
+pub fn combinations(cards: &[Card], size: usize) -> Vec<Vec<Card>> {
+    if size == 0 {
+        return vec![vec![]];
+    }
+    if cards.len() < size {
+        return vec![];
+    }
+
+    let mut with_first: Vec<Vec<Card>> = combinations(&cards[1..], size - 1)
+        .into_iter()
+        .map(|mut combination| {
+            combination.insert(0, cards[0].clone());
+            combination
+        })
+        .collect();
+    with_first.extend(combinations(&cards[1..], size));
+    with_first
+}
+
+fn best_of(hands: Vec<Vec<Card>>, deck_type: &DeckType) -> Option<(HandRank, Vec<Card>)> {
+    hands
+        .into_iter()
+        .filter_map(|hand| evaluate_five(&hand, deck_type).map(|rank| (rank, hand)))
+        .max_by(|a, b| strength(&a.0, deck_type).cmp(&strength(&b.0, deck_type)))
+}
+
+/*
+ * Best five cards out of the hole cards and the board, any combination (Hold'em rules).
+ */
+pub fn best_hand(
+    hole_cards: &[Card],
+    board: &[Card],
+    deck_type: &DeckType,
+) -> Option<(HandRank, Vec<Card>)> {
+    let cards: Vec<Card> = hole_cards.iter().chain(board.iter()).cloned().collect();
+    best_of(combinations(&cards, 5), deck_type)
+}
+
+/*
+ * Best five cards using exactly two hole cards and three board cards (Omaha rules).
+ */
+pub fn best_omaha_hand(
+    hole_cards: &[Card],
+    board: &[Card],
+    deck_type: &DeckType,
+) -> Option<(HandRank, Vec<Card>)> {
+    let board_combinations = combinations(board, 3);
+    best_of(
+        combinations(hole_cards, 2)
+            .into_iter()
+            .flat_map(|hole| {
+                board_combinations
+                    .iter()
+                    .map(move |board| [hole.clone(), board.clone()].concat())
+            })
+            .collect(),
+        deck_type,
+    )
+}
+
+/*
+ * A-5 lowball for the 8-or-better low: the Ace counts as 1, straights and flushes are ignored and the
+ * hand only qualifies with five distinct ranks of 8 or below. The values are sorted from the highest,
+ * so a smaller vector is a better low (8-6-4-3-2 beats 8-7-3-2-A).
+ */
+pub fn evaluate_low(cards: &[Card]) -> Option<Vec<u8>> {
+    let mut values: Vec<u8> = cards.iter().map(|card| card.rank()).collect();
+    values.sort_unstable_by(|a, b| b.cmp(a));
+
+    let distinct = values.windows(2).all(|pair| pair[0] != pair[1]);
+    if values.len() == 5 && distinct && values[0] <= 8 {
+        Some(values)
+    } else {
+        None
+    }
+}
+
+/*
+ * Best qualifying low using exactly two hole cards and three board cards (Omaha Hi-Lo rules).
+ */
+pub fn best_omaha_low(hole_cards: &[Card], board: &[Card]) -> Option<(Vec<u8>, Vec<Card>)> {
+    let board_combinations = combinations(board, 3);
+    combinations(hole_cards, 2)
+        .into_iter()
+        .flat_map(|hole| {
+            board_combinations
+                .iter()
+                .map(move |board| [hole.clone(), board.clone()].concat())
+        })
+        .filter_map(|hand| evaluate_low(&hand).map(|low| (low, hand)))
+        .min_by(|a, b| a.0.cmp(&b.0))
+}
+
+/*
+ * Indexes of the best entries, several of them when the pot is split.
+ */
+pub fn winners<T: Ord>(ranks: &[T]) -> Vec<usize> {
+    let mut winners: Vec<usize> = Vec::new();
+    for (i, rank) in ranks.iter().enumerate() {
+        match winners.first().map(|&best| rank.cmp(&ranks[best])) {
+            None | Some(Ordering::Equal) => winners.push(i),
+            Some(Ordering::Greater) => winners = vec![i],
+            Some(Ordering::Less) => {}
+        }
+    }
+    winners
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::cmp::Reverse;
+
+    use super::*;
+    use crate::state::Deck;
+
+    const STANDARD: &DeckType = &DeckType::Standard;
+    const SHORT_DECK: &DeckType = &DeckType::ShortDeck;
+
+    // "As Td 9c" style notation, suits ordered as in Card's Display: clubs, diamonds, hearts, spades
+    pub fn cards(notation: &str) -> Vec<Card> {
+        notation
+            .split_whitespace()
+            .map(|card| {
+                let (rank, suit) = card.split_at(card.len() - 1);
+                let rank = match rank {
+                    "A" => 1,
+                    "T" => 10,
+                    "J" => 11,
+                    "Q" => 12,
+                    "K" => 13,
+                    digit => digit.parse().unwrap(),
+                };
+                let suit = "cdhs".find(suit).unwrap() as u8;
+                Card::new(suit, rank)
+            })
+            .collect()
+    }
+
+    fn rank(notation: &str) -> HandRank {
+        evaluate_five(&cards(notation), STANDARD).unwrap()
+    }
+
+    #[test]
+    fn categories() {
+        let cases = [
+            ("As Ks Qs Js Ts", HandCategory::StraightFlush, vec![14]),
+            ("5d 4d 3d 2d Ad", HandCategory::StraightFlush, vec![5]),
+            ("9c 9d 9h 9s 2c", HandCategory::FourOfAKind, vec![9, 2]),
+            ("Kc Kd Kh 4s 4c", HandCategory::FullHouse, vec![13, 4]),
+            ("Ah Jh 8h 4h 2h", HandCategory::Flush, vec![14, 11, 8, 4, 2]),
+            ("Tc 9d 8h 7s 6c", HandCategory::Straight, vec![10]),
+            ("Ac Kd Qh Js Tc", HandCategory::Straight, vec![14]),
+            ("5c 4d 3h 2s Ac", HandCategory::Straight, vec![5]),
+            ("7c 7d 7h Ks 2c", HandCategory::ThreeOfAKind, vec![7, 13, 2]),
+            ("Jc Jd 3h 3s Ac", HandCategory::TwoPair, vec![11, 3, 14]),
+            ("Ac Ad 9h 5s 2c", HandCategory::OnePair, vec![14, 9, 5, 2]),
+            ("Kc Jd 9h 5s 2c", HandCategory::HighCard, vec![13, 11, 9, 5, 2]),
+        ];
+        for (notation, category, kickers) in cases {
+            assert_eq!(rank(notation), HandRank { category, kickers }, "{}", notation);
+        }
+    }
+
+    #[test]
+    fn not_straights() {
+        // no wrap-around through the Ace
+        assert_eq!(rank("Qc Kd Ah 2s 3c").category, HandCategory::HighCard);
+        assert_eq!(rank("Ac Kd Qh Js 9c").category, HandCategory::HighCard);
+    }
+
+    #[test]
+    fn ordering() {
+        let ordered = [
+            "Kc Jd 9h 5s 2c",
+            "Ac Qd Jh 9s 8c",
+            "2c 2d 3h 4s 5h",
+            "Ac Ad Kh Qs Jc",
+            "3c 3d 2h 2s Ac",
+            "Ac Ad 2h 2s 3c",
+            "Ac Ad Kh Ks 2c",
+            "2c 2d 2h 4s 5c",
+            "5c 4d 3h 2s Ac",
+            "6c 5d 4h 3s 2c",
+            "Ac Kd Qh Js Tc",
+            "7h 5h 4h 3h 2h",
+            "Ah Kh Qh Jh 9h",
+            "2c 2d 2h 3s 3c",
+            "Ac Ad Ah Ks Kc",
+            "2c 2d 2h 2s 3c",
+            "Ac Ad Ah As Kc",
+            "5d 4d 3d 2d Ad",
+            "Kd Qd Jd Td 9d",
+            "As Ks Qs Js Ts",
+        ];
+        for pair in ordered.windows(2) {
+            assert!(rank(pair[0]) < rank(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn short_deck() {
+        // A-6-7-8-9 is the short deck wheel, and still no straight on a standard deck
+        let wheel = evaluate_five(&cards("Ac 6d 7h 8s 9c"), SHORT_DECK).unwrap();
+        assert_eq!(wheel, HandRank { category: HandCategory::Straight, kickers: vec![9] });
+        assert_eq!(rank("Ac 6d 7h 8s 9c").category, HandCategory::HighCard);
+        let six_high = evaluate_five(&cards("6c 7d 8h 9s Tc"), SHORT_DECK).unwrap();
+        assert!(strength(&wheel, SHORT_DECK) < strength(&six_high, SHORT_DECK));
+        let straight_flush = evaluate_five(&cards("Ah 6h 7h 8h 9h"), SHORT_DECK).unwrap();
+        assert_eq!(straight_flush, HandRank { category: HandCategory::StraightFlush, kickers: vec![9] });
+
+        // a flush beats a full house, the other categories keep their order
+        let flush = rank("Ah Jh 8h 7h 6h");
+        let full_house = rank("Kc Kd Kh 7s 7c");
+        let four_of_a_kind = rank("6c 6d 6h 6s 7c");
+        assert!(full_house > flush);
+        assert!(strength(&flush, SHORT_DECK) > strength(&full_house, SHORT_DECK));
+        assert!(strength(&four_of_a_kind, SHORT_DECK) > strength(&flush, SHORT_DECK));
+    }
+
+    #[test]
+    fn not_five_cards() {
+        assert_eq!(evaluate_five(&cards("Ac Kd Qh Js"), STANDARD), None);
+        assert_eq!(best_hand(&cards("Ac Kd"), &cards("Qh Js"), STANDARD), None);
+    }
+
+    #[test]
+    fn equal_hands_tie() {
+        assert_eq!(rank("Ac Kd Qh Js 9c"), rank("Ad Kh Qs Jc 9d"));
+        assert_eq!(rank("Tc 9d 8h 7s 6c"), rank("Td 9h 8s 7c 6d"));
+    }
+
+    #[test]
+    fn best_of_seven() {
+        // board plays: both players hold a straight on the board
+        let board = cards("5c 6d 7h 8s 9c");
+        let (rank_1, _) = best_hand(&cards("2c 2d"), &board, STANDARD).unwrap();
+        let (rank_2, _) = best_hand(&cards("Ah Kh"), &board, STANDARD).unwrap();
+        assert_eq!(rank_1, rank_2);
+        assert_eq!(winners(&[rank_1, rank_2]), vec![0, 1]);
+
+        // two trips in seven cards make a full house with the higher trips
+        let (full_house, hand) = best_hand(&cards("Kc Kd"), &cards("Kh 4s 4c 4d 9h"), STANDARD).unwrap();
+        assert_eq!(full_house, HandRank { category: HandCategory::FullHouse, kickers: vec![13, 4] });
+        assert_eq!(hand.len(), 5);
+
+        // three pairs: the best two pairs with the best remaining kicker
+        let (two_pair, _) = best_hand(&cards("Qc Qd"), &cards("Jh Js 3c 3d 2h"), STANDARD).unwrap();
+        assert_eq!(two_pair, HandRank { category: HandCategory::TwoPair, kickers: vec![12, 11, 3] });
+
+        // a flush beats the straight available on the same cards
+        let (flush, _) = best_hand(&cards("Ah 2h"), &cards("3h 4c 5h 9h 6s"), STANDARD).unwrap();
+        assert_eq!(flush.category, HandCategory::Flush);
+
+        // kickers decide between the same pair
+        let (ace_kicker, _) = best_hand(&cards("Ac Qd"), &cards("Kh Ks 8c 5d 2h"), STANDARD).unwrap();
+        let (jack_kicker, _) = best_hand(&cards("Jc Td"), &cards("Kh Ks 8c 5d 2h"), STANDARD).unwrap();
+        assert_eq!(winners(&[jack_kicker, ace_kicker]), vec![1]);
+    }
+
+    #[test]
+    fn omaha_uses_exactly_two_hole_cards() {
+        // four spades in hand and one on the board is not a flush in Omaha
+        let hole = cards("As Ks Qs Js");
+        let board = cards("2s 7d 8c 9h 3d");
+        assert_eq!(best_hand(&hole, &board, STANDARD).unwrap().0.category, HandCategory::Flush);
+        assert_eq!(best_omaha_hand(&hole, &board, STANDARD).unwrap().0.category, HandCategory::HighCard);
+
+        // a board with four of a kind only gives trips with one matching card in hand
+        let (rank, hand) = best_omaha_hand(&cards("7c Ah Kd Qs"), &cards("7d 7h 7s 2c 3d"), STANDARD).unwrap();
+        assert_eq!(rank.category, HandCategory::FourOfAKind);
+        assert_eq!(hand.iter().filter(|card| card.rank() == 7).count(), 4);
+        let (rank, _) = best_omaha_hand(&cards("Ac Ah Kd Qs"), &cards("7d 7h 7s 7c 3d"), STANDARD).unwrap();
+        assert_eq!(rank, HandRank { category: HandCategory::FullHouse, kickers: vec![7, 14] });
+    }
+
+    #[test]
+    fn low_qualification() {
+        assert_eq!(evaluate_low(&cards("8c 4d 3h 2s Ac")), Some(vec![8, 4, 3, 2, 1]));
+        // straights and flushes do not hurt a low
+        assert_eq!(evaluate_low(&cards("5h 4h 3h 2h Ah")), Some(vec![5, 4, 3, 2, 1]));
+        // a pair or a card above 8 does not qualify
+        assert_eq!(evaluate_low(&cards("8c 8d 3h 2s Ac")), None);
+        assert_eq!(evaluate_low(&cards("9c 4d 3h 2s Ac")), None);
+        // the highest card decides first
+        assert!(evaluate_low(&cards("8c 6d 4h 3s 2c")) < evaluate_low(&cards("8c 7d 3h 2s Ac")));
+        assert!(evaluate_low(&cards("7c 6d 5h 4s 3c")) < evaluate_low(&cards("8c 4d 3h 2s Ac")));
+    }
+
+    #[test]
+    fn omaha_low() {
+        let (low, hand) = best_omaha_low(&cards("Ac 2d Kh Ks"), &cards("3c 4d 8h Qs Js")).unwrap();
+        assert_eq!(low, vec![8, 4, 3, 2, 1]);
+        assert_eq!(hand.len(), 5);
+
+        // only three low cards on the board are usable, a fourth low hole card does not help
+        assert_eq!(best_omaha_low(&cards("Ac 2d 3h 4s"), &cards("5c 9d Th Js Qc")), None);
+
+        // fewer than three distinct low cards on the board: no low at all
+        assert_eq!(best_omaha_low(&cards("Ac 2d 3h 4s"), &cards("5c 5d Th Js 6c")), None);
+
+        // counterfeited: the board pairs the A-2, which drops to the 8-7-5 low
+        let board = cards("Ah 2s 5c 7d 8h");
+        let (counterfeited, _) = best_omaha_low(&cards("Ac 2d Kh Qs"), &board).unwrap();
+        assert_eq!(counterfeited, vec![8, 7, 5, 2, 1]);
+        let (wheel, _) = best_omaha_low(&cards("3c 4d Kc Kd"), &board).unwrap();
+        assert_eq!(wheel, vec![5, 4, 3, 2, 1]);
+        assert_eq!(winners(&[Reverse(counterfeited), Reverse(wheel)]), vec![1]);
+    }
+
+    #[test]
+    fn all_five_card_hands() {
+        // the category frequencies over the C(52, 5) = 2598960 hands
+        let deck = Deck::new().cards;
+        let mut counts = [0u32; 9];
+        for a in 0..48 {
+            for b in (a + 1)..49 {
+                for c in (b + 1)..50 {
+                    for d in (c + 1)..51 {
+                        for e in (d + 1)..52 {
+                            let hand = [
+                                deck[a].clone(),
+                                deck[b].clone(),
+                                deck[c].clone(),
+                                deck[d].clone(),
+                                deck[e].clone(),
+                            ];
+                            counts[evaluate_five(&hand, STANDARD).unwrap().category as usize] += 1;
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(counts, [1302540, 1098240, 123552, 54912, 10200, 5108, 3744, 624, 40]);
+    }
+}
@@ -1,6 +1,11 @@
+pub mod betting;
 pub mod contract;
 mod error;
+pub mod evaluator;
 pub mod msg;
+pub mod pot;
+pub mod shamir;
+pub mod snip20;
 pub mod state;
 
 pub use crate::error::ContractError;
@@ -1,13 +1,23 @@
-use cosmwasm_std::Timestamp;
+use cosmwasm_std::{Addr, Binary, Timestamp, Uint128};
 use secret_toolkit_permit::Permit;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::state::{Card, GameState};
+use crate::evaluator::HandCategory;
+use crate::state::{Card, Currency, DeckType, GameState, GameVariant, Pause, PlayerAction, RakeConfig};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct InstantiateMsg {
+    #[serde(default)]
+    pub accepted_token: Option<TokenContractInfo>, // SNIP-20 token accepted for the escrowed buy-ins
+    #[serde(default)]
+    pub rake: Option<RakeConfig>,
+}
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TokenContractInfo {
+    pub address: String,
+    pub code_hash: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -15,6 +25,17 @@ pub struct StartGamePlayer {
     pub username: String,
     pub player_id: Uuid,
     pub public_key: String,
+    #[serde(default)]
+    pub stack: Uint128, // chips brought to the hand, only used when the betting is on-chain
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BettingConfig {
+    pub small_blind: Uint128,
+    pub big_blind: Uint128,
+    pub button: u32, // index of the dealer in the players list
+    #[serde(default)]
+    pub escrow: Option<Currency>, // take the stacks from the players' escrowed balances and pay the winnings out in that currency
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -25,6 +46,18 @@ pub enum ExecuteMsg {
         hand_ref: u32,
         players: Vec<StartGamePlayer>,
         prev_hand_showdown_players: Vec<Uuid>, // player_ids of players who showed their cards in the last hand
+        #[serde(default)]
+        threshold: Option<u32>, // k-of-n Shamir sharing of the community secrets, None keeps the n-of-n additive sharing
+        #[serde(default)]
+        game_variant: GameVariant, // controls the number of hole cards, hold_em by default
+        #[serde(default)]
+        deck_type: DeckType, // standard 52-card deck by default
+        #[serde(default)]
+        burn_cards: bool, // burn a card before each community street, like a live dealer
+        #[serde(default)]
+        betting: Option<BettingConfig>, // no-limit betting rounds played on-chain, the streets are then revealed by the betting
+        #[serde(default)]
+        force: bool, // abort the previous hand if it didn't reach the showdown, its escrowed chips go back to the players
     },
     CommunityCards {
         table_id: u32,
@@ -34,7 +67,124 @@ pub enum ExecuteMsg {
         table_id: u32,
         game_state: GameState,
         showdown_player_ids: Vec<Uuid>, // player_ids of players whos cards are shown
+        #[serde(default)]
+        contributions: Vec<Contribution>, // chips put in over the hand by every player, folded ones included; tables with on-chain betting use their own
+        #[serde(default)]
+        pot: Option<Uint128>, // pot size the rake is computed from, the contributions are used when it is not given
+    },
+    RunItMultiple {
+        table_id: u32,
+        game_state: GameState, // street on which the players went all-in
+        runs: u32, // total number of boards, including the one already dealt
+    },
+    SubmitShare {
+        table_id: u32,
+        phase: GameState,
+        #[serde(deserialize_with = "string_to_u64")]
+        share: u64,
+    },
+    Fold {
+        table_id: u32,
+    },
+    Check {
+        table_id: u32,
+    },
+    Call {
+        table_id: u32,
+    },
+    Bet {
+        table_id: u32,
+        amount: Uint128,
+    },
+    Raise {
+        table_id: u32,
+        amount: Uint128, // total street bet raised to
+    },
+    AllIn {
+        table_id: u32,
+    },
+    /*
+     * Refused while the contract still holds some of the current token.
+     */
+    SetAcceptedToken {
+        token: Option<TokenContractInfo>,
+    },
+    /*
+     * SNIP-20 Send callback, `from` buys into a table with `amount` tokens, `msg` being a ReceiveMsg.
+     */
+    Receive {
+        sender: String,
+        from: String,
+        amount: Uint128,
+        #[serde(default)]
+        memo: Option<String>,
+        msg: Option<Binary>,
+    },
+    Withdraw {
+        table_id: u32,
     },
+    JoinTable {
+        table_id: u32, // the uscrt sent with the message are added to the sender's native balance
+    },
+    LeaveTable {
+        table_id: u32,
+    },
+    SetRake {
+        rake: Option<RakeConfig>,
+    },
+    WithdrawRake {
+        recipient: Option<String>, // the owner by default
+    },
+    AddOperator {
+        address: String,
+    },
+    RemoveOperator {
+        address: String,
+    },
+    /*
+     * Proposes a new owner, the ownership only moves once they send AcceptOwnership.
+     */
+    TransferOwnership {
+        new_owner: String,
+    },
+    AcceptOwnership {},
+    /*
+     * Cancels a misdealt or interrupted hand: the deck and every hole card are published
+     * and the escrowed chips go back to the players.
+     */
+    VoidHand {
+        table_id: u32,
+        reason: String,
+    },
+    CloseTable {
+        table_id: u32,
+        #[serde(default)]
+        showdown_player_ids: Vec<Uuid>, // players who showed their cards in the final hand
+    },
+    /*
+     * Closes the tables without activity (hand start or reveal) since `older_than`, looking at `limit` tables
+     * after `start_after` in the table index. The `start_after` attribute of the response continues the sweep.
+     */
+    PruneTables {
+        older_than: Timestamp,
+        #[serde(default)]
+        start_after: Option<u32>, // table_id
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    SetPaused {
+        paused: bool,
+        #[serde(default)]
+        reason: String,
+        #[serde(default)]
+        all_execution: bool, // reject every message but SetPaused, not only StartGame
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    BuyIn { table_id: u32 },
 }
 /*
 * The secrets are sent as strings because javascript is using 53-bit integers. 
@@ -47,11 +197,23 @@ pub enum QueryMsg {
         permit: Permit,
         query: QueryWithPermit,
     },
+    Config {},
+    /*
+     * Pages through the tables in the order they were opened, a closed table's place going to the last one.
+     */
+    Tables {
+        start_after: Option<u32>, // table_id
+        limit: Option<u32>,
+    },
+    HandHistory {
+        table_id: u32,
+        start_after: Option<u32>, // hand_ref
+        limit: Option<u32>,
+    },
     CommunityCards { 
         table_id: u32, 
         game_state: GameState, 
-        #[serde(deserialize_with = "string_to_u64")]
-        secret_key: u64 
+        shares: Vec<SecretShare>, // all the shares for additive tables, at least `threshold` of them for Shamir tables
     },
     Showdown { 
         table_id: u32, 
@@ -66,6 +228,19 @@ pub enum QueryMsg {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Contribution {
+    pub player_id: Uuid,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SecretShare {
+    pub index: u32,
+    #[serde(deserialize_with = "string_to_u64")]
+    pub share: u64,
+}
+
 fn string_to_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -99,6 +274,42 @@ where
 #[serde(rename_all = "snake_case")]
 pub enum QueryWithPermit {
     PlayerPrivateData { table_id: u32 },
+    Balance { table_id: u32 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BalanceResponse {
+    pub table_id: u32,
+    pub balance: Uint128, // escrowed tokens, not counting the chips in the current hand
+    pub native_balance: Uint128, // escrowed uscrt
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ConfigResponse {
+    pub owner: Addr,
+    pub pending_owner: Option<Addr>,
+    pub operators: Vec<Addr>,
+    pub pause: Option<Pause>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TablesResponse {
+    pub tables: Vec<TableInfo>, // by table_id
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TableInfo {
+    pub table_id: u32,
+    pub hand_ref: u32,
+    pub player_count: u32,
+    pub game_state: GameState,
+    pub last_activity: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HandHistoryResponse {
+    pub table_id: u32,
+    pub hands: Vec<LastHandLogResponse>, // oldest first
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -107,6 +318,7 @@ pub struct PlayerDataResponse {
     pub hand_ref: u32,
     pub hand: Vec<Card>,
     pub hand_secret: String,
+    pub share_index: u32,
     pub flop_secret_share: String,
     pub turn_secret_share: String,
     pub river_secret_share: String,
@@ -119,6 +331,7 @@ pub enum ResponsePayload {
     LastHand(LastHandLogResponse),
     CommunityCards(CommunityCardsResponse),
     Showdown(ShowdownResponse),
+    Action(ActionResponse),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -126,6 +339,9 @@ pub struct StartGameResponse {
     pub table_id: u32,
     pub hand_ref: u32,
     pub players: Vec<String>,
+    pub game_variant: GameVariant,
+    pub deck_type: DeckType,
+    pub deck_commitment: Binary, // SHA-256(deck_salt || deck), both revealed in the next hand's log
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -136,12 +352,70 @@ pub struct CommunityCardsResponse {
     pub community_cards: Vec<Card>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ActionResponse {
+    pub table_id: u32,
+    pub hand_ref: u32,
+    pub player_id: Uuid,
+    pub action: PlayerAction,
+    pub amount: Uint128, // chips put in the pot by the action
+    pub pot: Uint128,
+    pub game_state: GameState,
+    pub to_act: Option<Uuid>, // None once the betting of the street is over
+    pub community_cards: Vec<Card>, // street revealed when the action closed the betting round
+    pub winner: Option<Uuid>, // everybody else folded, the pot went to this player
+    pub rake: Uint128, // taken from the pot when the hand ended uncontested after the flop
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct ShowdownResponse {
     pub table_id: u32,
     pub hand_ref: u32,
     pub players_cards: Vec<(Uuid, Vec<Card>)>,
     pub community_cards: Option<Vec<Card>>,
+    pub boards: Vec<Vec<Card>>, // every all-in runout, the first one is community_cards
+    pub results: Vec<BoardResult>, // one per complete board, computed by the Showdown execute
+    pub pots: Vec<PotResult>, // main pot first, then the side pots
+    pub uncalled: Vec<Payout>, // chips nobody matched, given back
+    pub payouts: Vec<Payout>, // total won by each player, uncalled chips included
+    pub rake: Uint128, // already taken out of the pots and payouts
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PotResult {
+    pub amount: Uint128, // after the rake
+    pub eligible: Vec<Uuid>,
+    pub payouts: Vec<Payout>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Payout {
+    pub player_id: Uuid,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BoardResult {
+    pub board: Vec<Card>,
+    pub hands: Vec<RankedHand>,
+    pub winners: Vec<Uuid>, // high hand winners, several players when the pot is split
+    pub low_hands: Vec<LowHand>, // Hi-Lo variants only, players without a qualifying low are left out
+    pub low_winners: Vec<Uuid>, // empty when nobody qualifies for the low, the high hand then takes the pot
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LowHand {
+    pub player_id: Uuid,
+    pub low: Vec<u8>, // card ranks from the highest, the Ace counts as 1
+    pub best_hand: Vec<Card>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RankedHand {
+    pub player_id: Uuid,
+    pub category: HandCategory,
+    pub kickers: Vec<u8>, // card values, 14 for the Ace (5 for the top of the 5-high straight, 9 for the short deck's A-6-7-8-9)
+    pub best_hand: Vec<Card>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -152,10 +426,24 @@ pub struct ShowdownPlayer {
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct LastHandLogResponse {
+    pub hand_ref: u32,
     pub showdown_players: Vec<ShowdownPlayer>, 
     pub community_cards: Vec<String>,
     pub flop_retrieved_at: Option<Timestamp>,
     pub turn_retrieved_at: Option<Timestamp>,
     pub river_retrieved_at: Option<Timestamp>,
     pub showdown_retrieved_at: Option<Timestamp>,
+    /*
+     * Opening of the deck_commitment published when this hand started. The deck is the byte encoding of the
+     * shuffled cards (see state::Card); cards are dealt from the end: the variant's hole cards to each
+     * player in the StartGame order, then the flop, the turn and the river, each preceded by one of the
+     * burned_cards when burning was enabled for the hand.
+     */
+    pub deck_salt: Binary,
+    pub deck: Binary,
+    pub deck_type: DeckType,
+    pub burned_cards: Vec<String>,
+    pub extra_runs: Vec<Vec<String>>, // boards dealt after the river when the hand was run multiple times, their burns follow in burned_cards
+    #[serde(default)]
+    pub void_reason: Option<String>, // why the hand was voided, None for a hand that was played out
 }
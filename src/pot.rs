@@ -0,0 +1,257 @@
+/*
+* Main and side pots of a hand, built from what every player put in.
+* Each level of the showdown players' contributions closes a pot: every player contributes up to that
+* level and only the players who matched it can win it. Chips above the second highest contribution
+* were never called and go back to their owner.
+* Odd chips go to the first winners in seat order, which is the order the players are passed in.
+*/
+use cosmwasm_std::Uint128;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pot {
+    pub amount: Uint128,
+    pub eligible: Vec<Uuid>,
+}
+
+/*
+ * `contributions` holds every player who put chips in, folded or not, `eligible` the players
+ * still in the hand at showdown. Returns the pots from the main pot up and the uncalled chips.
+ */
+pub fn build_pots(contributions: &[(Uuid, Uint128)], eligible: &[Uuid]) -> (Vec<Pot>, Vec<(Uuid, Uint128)>) {
+    let contribution = |player_id: &Uuid| {
+        contributions
+            .iter()
+            .find(|(id, _)| id == player_id)
+            .map(|(_, amount)| *amount)
+            .unwrap_or_default()
+    };
+    let mut amounts: Vec<Uint128> = contributions.iter().map(|(_, amount)| *amount).collect();
+    amounts.sort_by(|a, b| b.cmp(a));
+    let called = amounts.get(1).copied().unwrap_or_default();
+
+    let mut levels: Vec<Uint128> = eligible
+        .iter()
+        .map(|player_id| contribution(player_id).min(called))
+        .filter(|amount| !amount.is_zero())
+        .collect();
+    levels.sort();
+    levels.dedup();
+
+    let mut pots = Vec::new();
+    let mut previous = Uint128::zero();
+    for &level in levels.iter() {
+        let amount = contributions
+            .iter()
+            .map(|(_, amount)| (*amount).min(level) - (*amount).min(previous))
+            .sum();
+        let eligible = contributions
+            .iter()
+            .filter(|(id, amount)| *amount >= level && eligible.contains(id))
+            .map(|(id, _)| *id)
+            .collect();
+        pots.push(Pot { amount, eligible });
+        previous = level;
+    }
+
+    let uncalled = contributions
+        .iter()
+        .filter(|(_, amount)| *amount > previous)
+        .map(|(id, amount)| (*id, *amount - previous))
+        .collect();
+    (pots, uncalled)
+}
+
+/*
+ * Splits `amount` in `parts` shares, the first shares get one more chip each until the remainder is gone.
+ */
+pub fn divide(amount: Uint128, parts: usize) -> Vec<Uint128> {
+    if parts == 0 {
+        return vec![];
+    }
+    let parts_count = Uint128::from(parts as u128);
+    let share = amount / parts_count;
+    let remainder = (amount % parts_count).u128() as usize;
+    (0..parts)
+        .map(|i| if i < remainder { share + Uint128::one() } else { share })
+        .collect()
+}
+
+/*
+ * Awards every pot. The pot is divided between the boards of a run-it-multiple hand, then each board share
+ * goes to the high hand, or is halved between the high and the low when `winners` returns low winners.
+ * `winners(board, eligible)` returns the (high, low) winners of the board among the eligible players.
+ */
+pub fn resolve<F>(pots: &[Pot], boards: usize, mut winners: F) -> Vec<Vec<(Uuid, Uint128)>>
+where
+    F: FnMut(usize, &[Uuid]) -> (Vec<Uuid>, Vec<Uuid>),
+{
+    pots.iter()
+        .map(|pot| {
+            let mut payouts: Vec<(Uuid, Uint128)> = Vec::new();
+            for (board, board_share) in divide(pot.amount, boards).into_iter().enumerate() {
+                let (high, low) = winners(board, &pot.eligible);
+                let halves = if low.is_empty() {
+                    vec![(board_share, high)]
+                } else {
+                    let shares = divide(board_share, 2);
+                    vec![(shares[0], high), (shares[1], low)]
+                };
+                for (amount, winners) in halves {
+                    let winners = in_seat_order(&pot.eligible, &winners);
+                    let shares = divide(amount, winners.len());
+                    for (player_id, share) in winners.into_iter().zip(shares) {
+                        add_payout(&mut payouts, player_id, share);
+                    }
+                }
+            }
+            payouts
+        })
+        .collect()
+}
+
+/*
+ * Rake of a pot in basis points of its size, capped. Uncalled chips are not part of the pot.
+ */
+pub fn rake(pot: Uint128, basis_points: u16, cap: Uint128) -> Uint128 {
+    pot.multiply_ratio(basis_points as u128, 10_000u128).min(cap)
+}
+
+/*
+ * Takes the rake out of the pots from the main pot up, returns what was actually taken.
+ */
+pub fn take_rake(pots: &mut [Pot], rake: Uint128) -> Uint128 {
+    let mut left = rake;
+    for pot in pots.iter_mut() {
+        let taken = left.min(pot.amount);
+        pot.amount -= taken;
+        left -= taken;
+    }
+    rake - left
+}
+
+fn in_seat_order(seats: &[Uuid], players: &[Uuid]) -> Vec<Uuid> {
+    seats.iter().filter(|id| players.contains(id)).copied().collect()
+}
+
+fn add_payout(payouts: &mut Vec<(Uuid, Uint128)>, player_id: Uuid, amount: Uint128) {
+    match payouts.iter_mut().find(|(id, _)| *id == player_id) {
+        Some((_, total)) => *total += amount,
+        None => payouts.push((player_id, amount)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(i: u128) -> Uuid {
+        Uuid::from_u128(i)
+    }
+
+    fn chips(amounts: &[(u128, u128)]) -> Vec<(Uuid, Uint128)> {
+        amounts.iter().map(|&(i, amount)| (player(i), Uint128::new(amount))).collect()
+    }
+
+    #[test]
+    fn divide_odd_chips() {
+        assert_eq!(divide(Uint128::new(10), 3), vec![Uint128::new(4), Uint128::new(3), Uint128::new(3)]);
+        assert_eq!(divide(Uint128::new(11), 3), vec![Uint128::new(4), Uint128::new(4), Uint128::new(3)]);
+        assert_eq!(divide(Uint128::new(7), 1), vec![Uint128::new(7)]);
+        assert!(divide(Uint128::new(7), 0).is_empty());
+    }
+
+    #[test]
+    fn three_way_all_in() {
+        // 1 all-in for 50, 2 for 120, 3 covers with 200 of which 80 are uncalled
+        let contributions = chips(&[(1, 50), (2, 120), (3, 200)]);
+        let eligible = [player(1), player(2), player(3)];
+        let (pots, uncalled) = build_pots(&contributions, &eligible);
+        assert_eq!(
+            pots,
+            vec![
+                Pot { amount: Uint128::new(150), eligible: eligible.to_vec() },
+                Pot { amount: Uint128::new(140), eligible: vec![player(2), player(3)] },
+            ]
+        );
+        assert_eq!(uncalled, chips(&[(3, 80)]));
+
+        // 1 and 3 tie for the main pot, 2 wins the side pot, the odd chip goes to the first seat
+        let (pots, _) = build_pots(&chips(&[(1, 51), (2, 120), (3, 120)]), &eligible);
+        let payouts = resolve(&pots, 1, |_, eligible| {
+            if eligible.contains(&player(1)) {
+                (vec![player(3), player(1)], vec![])
+            } else {
+                (vec![player(2)], vec![])
+            }
+        });
+        assert_eq!(payouts, vec![chips(&[(1, 77), (3, 76)]), chips(&[(2, 138)])]);
+    }
+
+    #[test]
+    fn four_way_all_in_with_a_fold() {
+        // 4 put 30 in and folded, 1 is all-in for 25, 2 for 101, 3 calls 101
+        let contributions = chips(&[(1, 25), (2, 101), (3, 101), (4, 30)]);
+        let eligible = [player(1), player(2), player(3)];
+        let (pots, uncalled) = build_pots(&contributions, &eligible);
+        assert_eq!(
+            pots,
+            vec![
+                Pot { amount: Uint128::new(100), eligible: eligible.to_vec() },
+                Pot { amount: Uint128::new(157), eligible: vec![player(2), player(3)] },
+            ]
+        );
+        assert!(uncalled.is_empty());
+
+        // 1 has the best hand, 2 and 3 chop the side pot and its odd chip goes to 2
+        let payouts = resolve(&pots, 1, |_, eligible| {
+            if eligible.contains(&player(1)) {
+                (vec![player(1)], vec![])
+            } else {
+                (vec![player(2), player(3)], vec![])
+            }
+        });
+        assert_eq!(payouts, vec![chips(&[(1, 100)]), chips(&[(2, 79), (3, 78)])]);
+        let total: Uint128 = payouts.iter().flatten().map(|(_, amount)| *amount).sum();
+        assert_eq!(total, Uint128::new(257));
+    }
+
+    #[test]
+    fn four_way_all_in_every_level() {
+        let contributions = chips(&[(1, 10), (2, 20), (3, 35), (4, 35)]);
+        let eligible = [player(1), player(2), player(3), player(4)];
+        let (pots, uncalled) = build_pots(&contributions, &eligible);
+        let amounts: Vec<_> = pots.iter().map(|pot| pot.amount.u128()).collect();
+        assert_eq!(amounts, vec![40, 30, 30]);
+        assert_eq!(pots[2].eligible, vec![player(3), player(4)]);
+        assert!(uncalled.is_empty());
+    }
+
+    #[test]
+    fn rake_is_capped_and_taken_from_the_main_pot() {
+        assert_eq!(rake(Uint128::new(1999), 500, Uint128::new(300)), Uint128::new(99));
+        assert_eq!(rake(Uint128::new(10_000), 500, Uint128::new(300)), Uint128::new(300));
+        assert_eq!(rake(Uint128::new(10_000), 0, Uint128::new(300)), Uint128::zero());
+
+        let (mut pots, _) = build_pots(&chips(&[(1, 10), (2, 40), (3, 40)]), &[player(1), player(2), player(3)]);
+        assert_eq!(take_rake(&mut pots, Uint128::new(35)), Uint128::new(35));
+        let amounts: Vec<_> = pots.iter().map(|pot| pot.amount.u128()).collect();
+        assert_eq!(amounts, vec![0, 55]);
+        assert_eq!(take_rake(&mut pots, Uint128::new(100)), Uint128::new(55));
+    }
+
+    #[test]
+    fn hi_lo_and_multiple_boards() {
+        let contributions = chips(&[(1, 101), (2, 101), (3, 101)]);
+        let eligible = [player(1), player(2), player(3)];
+        let (pots, _) = build_pots(&contributions, &eligible);
+        assert_eq!(pots.len(), 1);
+
+        // two boards of 152 and 151: 1 scoops the first, the second is split between 2 (high) and 3 (low)
+        let payouts = resolve(&pots, 2, |board, _| match board {
+            0 => (vec![player(1)], vec![]),
+            _ => (vec![player(2)], vec![player(3)]),
+        });
+        assert_eq!(payouts, vec![chips(&[(1, 152), (2, 76), (3, 75)])]);
+    }
+}
@@ -0,0 +1,149 @@
+/*
+* Shamir secret sharing over GF(p) with p = 2^61 - 1.
+* A secret below PRIME is hidden as the constant term of a random polynomial of degree threshold - 1,
+* player i (1-based) receives the evaluation at x = i, and any `threshold` shares recover the secret
+* by Lagrange interpolation at x = 0.
+*/
+pub const PRIME: u64 = (1 << 61) - 1;
+
+fn add(a: u64, b: u64) -> u64 {
+    ((a as u128 + b as u128) % PRIME as u128) as u64
+}
+
+fn sub(a: u64, b: u64) -> u64 {
+    ((a as u128 + PRIME as u128 - b as u128) % PRIME as u128) as u64
+}
+
+fn mul(a: u64, b: u64) -> u64 {
+    ((a as u128 * b as u128) % PRIME as u128) as u64
+}
+
+fn pow(mut base: u64, mut exponent: u64) -> u64 {
+    let mut result = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul(result, base);
+        }
+        base = mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn inverse(a: u64) -> u64 {
+    pow(a, PRIME - 2)
+}
+
+/*
+ * `coefficients` are the random non-constant coefficients of the polynomial (threshold - 1 of them),
+ * they are reduced modulo PRIME so raw random u64 values can be passed in.
+ */
+pub fn split(secret: u64, coefficients: &[u64], share_count: u32) -> Vec<u64> {
+    (1..=share_count as u64)
+        .map(|x| {
+            coefficients
+                .iter()
+                .rev()
+                .chain(std::iter::once(&secret))
+                .fold(0, |acc, coefficient| add(mul(acc, x), coefficient % PRIME))
+        })
+        .collect()
+}
+
+/*
+ * Interpolates the polynomial going through `shares` (index, value) at x = 0.
+ * Returns None if an index is 0 or repeated, since the interpolation is undefined then.
+ */
+pub fn reconstruct(shares: &[(u32, u64)]) -> Option<u64> {
+    if shares.is_empty() {
+        return None;
+    }
+
+    let mut secret = 0;
+    for (i, &(x_i, y_i)) in shares.iter().enumerate() {
+        if x_i == 0 {
+            return None;
+        }
+        let mut numerator = 1;
+        let mut denominator = 1;
+        for (j, &(x_j, _)) in shares.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            if x_i == x_j {
+                return None;
+            }
+            numerator = mul(numerator, x_j as u64);
+            denominator = mul(denominator, sub(x_j as u64, x_i as u64));
+        }
+        secret = add(secret, mul(y_i % PRIME, mul(numerator, inverse(denominator))));
+    }
+    Some(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: u64 = 1_234_567_890_123_456_789;
+    const COEFFICIENTS: [u64; 2] = [u64::MAX - 42, 987_654_321_987_654_321];
+
+    fn indexed(shares: &[u64]) -> Vec<(u32, u64)> {
+        shares
+            .iter()
+            .enumerate()
+            .map(|(i, share)| (i as u32 + 1, *share))
+            .collect()
+    }
+
+    #[test]
+    fn field_inverse() {
+        for a in [1, 2, 3, 12345, PRIME - 1] {
+            assert_eq!(mul(a, inverse(a)), 1);
+        }
+    }
+
+    #[test]
+    fn reconstruct_with_all_shares() {
+        let shares = split(SECRET, &COEFFICIENTS, 5);
+        assert_eq!(reconstruct(&indexed(&shares)), Some(SECRET));
+    }
+
+    #[test]
+    fn reconstruct_with_missing_players() {
+        // threshold 3 out of 5: every subset of 3 or 4 players recovers the secret
+        let shares = indexed(&split(SECRET, &COEFFICIENTS, 5));
+        for a in 0..5 {
+            for b in (a + 1)..5 {
+                for c in (b + 1)..5 {
+                    let subset = [shares[a], shares[b], shares[c]];
+                    assert_eq!(reconstruct(&subset), Some(SECRET));
+                }
+            }
+            let without_a: Vec<_> = shares.iter().copied().filter(|s| s.0 != shares[a].0).collect();
+            assert_eq!(reconstruct(&without_a), Some(SECRET));
+        }
+    }
+
+    #[test]
+    fn below_threshold_does_not_reconstruct() {
+        let shares = indexed(&split(SECRET, &COEFFICIENTS, 5));
+        assert_ne!(reconstruct(&shares[..2]), Some(SECRET));
+        assert_ne!(reconstruct(&[shares[1], shares[4]]), Some(SECRET));
+    }
+
+    #[test]
+    fn tampered_share_does_not_reconstruct() {
+        let mut shares = indexed(&split(SECRET, &COEFFICIENTS, 3));
+        shares[1].1 = add(shares[1].1, 1);
+        assert_ne!(reconstruct(&shares), Some(SECRET));
+    }
+
+    #[test]
+    fn invalid_indices() {
+        let shares = indexed(&split(SECRET, &COEFFICIENTS, 3));
+        assert_eq!(reconstruct(&[]), None);
+        assert_eq!(reconstruct(&[(0, shares[0].1), shares[1], shares[2]]), None);
+        assert_eq!(reconstruct(&[shares[0], shares[0], shares[1]]), None);
+    }
+}
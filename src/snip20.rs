@@ -0,0 +1,48 @@
+/*
+* The SNIP-20 messages the contract sends to the accepted token: RegisterReceive once the token is configured,
+* so deposits made with Send call back the Receive handler, and Transfer to pay the players out.
+*/
+use cosmwasm_std::{CosmosMsg, StdResult, Uint128};
+use secret_toolkit_utils::HandleCallback;
+use serde::{Deserialize, Serialize};
+
+use crate::state::TokenContract;
+
+const BLOCK_SIZE: usize = 256;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Snip20ExecuteMsg {
+    RegisterReceive {
+        code_hash: String,
+        padding: Option<String>,
+    },
+    Transfer {
+        recipient: String,
+        amount: Uint128,
+        memo: Option<String>,
+        padding: Option<String>,
+    },
+}
+
+impl HandleCallback for Snip20ExecuteMsg {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+}
+
+pub fn register_receive_msg(token: &TokenContract, code_hash: String) -> StdResult<CosmosMsg> {
+    Snip20ExecuteMsg::RegisterReceive {
+        code_hash,
+        padding: None,
+    }
+    .to_cosmos_msg(token.code_hash.clone(), token.address.to_string(), None)
+}
+
+pub fn transfer_msg(token: &TokenContract, recipient: String, amount: Uint128) -> StdResult<CosmosMsg> {
+    Snip20ExecuteMsg::Transfer {
+        recipient,
+        amount,
+        memo: None,
+        padding: None,
+    }
+    .to_cosmos_msg(token.code_hash.clone(), token.address.to_string(), None)
+}
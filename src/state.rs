@@ -1,9 +1,14 @@
+use std::fmt;
+
 use secret_toolkit_serialization::Json;
-use secret_toolkit_storage::{Item, Keymap, KeymapBuilder, WithoutIter};
+use secret_toolkit_storage::{AppendStore, Item, Keymap, KeymapBuilder, WithoutIter};
 use serde::{Deserialize, Serialize};
-use cosmwasm_std::{Addr, StdError, StdResult, Storage, Timestamp};
+use cosmwasm_std::{Addr, Binary, StdError, StdResult, Storage, Timestamp, Uint128};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
+use crate::msg::LastHandLogResponse;
+
 pub const PREFIX_REVOKED_PERMITS: &str = "revoked_permits";
 
 pub static COUNTER_KEY: Item<u128> = Item::new(b"counter");
@@ -13,13 +18,161 @@ pub static CONFIG_KEY: Item<Config> = Item::new(b"config");
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Config {
     pub owner: Addr,
+    #[serde(default)]
+    pub pending_owner: Option<Addr>, // proposed by the owner, becomes the owner once accepted
+    #[serde(default)]
+    pub operators: Vec<Addr>, // run the tables alongside the owner
     pub contract_address: Addr,
+    #[serde(default)]
+    pub accepted_token: Option<TokenContract>, // SNIP-20 token held in escrow for the buy-ins
+    #[serde(default)]
+    pub rake: Option<RakeConfig>,
+    #[serde(default)]
+    pub pause: Option<Pause>, // set by the owner to stop the dealer
+}
+
+impl Config {
+    pub fn is_operator(&self, sender: &Addr) -> bool {
+        *sender == self.owner || self.operators.contains(sender)
+    }
+}
+
+/*
+ * A paused contract refuses new hands, the hands in flight can still be revealed and queried
+ * unless every execution is halted.
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Pause {
+    pub reason: String,
+    pub all_execution: bool,
+}
+
+/*
+ * Rake taken from every pot that saw a flop ("no flop no drop").
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RakeConfig {
+    pub basis_points: u16, // 500 takes 5% of the pot
+    pub cap: Uint128,
+}
+
+/*
+ * Rake collected from the escrow hands, per currency, until the owner withdraws it.
+ */
+pub static TREASURY: Keymap<Currency, Uint128, Json, WithoutIter> =
+            KeymapBuilder::new(b"treasury").without_iter().build();
+
+/*
+ * Rake of the hands without escrow: the chips stay off-chain, the operator collects it and
+ * WithdrawRake only reports and clears it.
+ */
+pub static OFF_CHAIN_RAKE: Item<Uint128> = Item::new(b"off_chain_rake");
+
+/*
+ * Amount of the accepted token held by the contract: the balances, the chips in play and the Token treasury.
+ * It goes up with the deposits and down with every transfer out, the accepted token can only change at zero.
+ */
+pub static TOKEN_HELD: Item<Uint128> = Item::new(b"token_held");
+
+pub fn load_token_held(storage: &dyn Storage) -> StdResult<Uint128> {
+    Ok(TOKEN_HELD.may_load(storage)?.unwrap_or_default())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TokenContract {
+    pub address: Addr,
+    pub code_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Currency {
+    Token, // the accepted SNIP-20 token, deposited with Send
+    Native, // uscrt sent along JoinTable
+}
+
+/*
+ * Escrowed chips of the players, keyed by (table_id, currency, player address).
+ * The chips brought to a hand leave the balance at StartGame and what is left of them comes back at the end of the hand.
+ */
+pub static BALANCES: Keymap<(u32, Currency, String), Uint128, Json, WithoutIter> =
+            KeymapBuilder::new(b"balances").without_iter().build();
+
+pub fn load_balance(storage: &dyn Storage, table_id: u32, currency: &Currency, player: &str) -> Uint128 {
+    BALANCES
+        .get(storage, &(table_id, currency.clone(), player.to_string()))
+        .unwrap_or_default()
+}
+
+pub fn save_balance(
+    storage: &mut dyn Storage,
+    table_id: u32,
+    currency: &Currency,
+    player: &str,
+    balance: Uint128,
+) -> StdResult<()> {
+    let key = (table_id, currency.clone(), player.to_string());
+    if balance.is_zero() {
+        return BALANCES.remove(storage, &key);
+    }
+    BALANCES.insert(storage, &key, &balance)
 }
 
 pub static TABLES_STORE: Keymap<u32, PokerTable, Json, WithoutIter> =
             KeymapBuilder::new(b"tables").without_iter().build();
 
+/*
+ * Ids of the stored tables, TABLES_STORE can't be iterated. They are listed in the order the tables were
+ * opened, a deleted id leaves its place to the last one, and TABLE_POSITIONS gives the place of every id
+ * so a page can start right after a given table.
+ * Built on every use rather than kept in a static, an AppendStore caches its length.
+ */
+fn table_index() -> AppendStore<'static, u32, Json> {
+    AppendStore::new(b"table_index")
+}
+
+pub static TABLE_POSITIONS: Keymap<u32, u32, Json, WithoutIter> =
+            KeymapBuilder::new(b"table_positions").without_iter().build();
+
+/*
+ * Operator of every table ever opened, kept when the table is closed or pruned so that another
+ * operator can't take over its table_id.
+ */
+static TABLE_OPERATORS: Keymap<u32, Addr, Json, WithoutIter> =
+            KeymapBuilder::new(b"table_operators").without_iter().build();
+
+pub fn table_operator(storage: &dyn Storage, table_id: u32) -> Option<Addr> {
+    TABLE_OPERATORS.get(storage, &table_id)
+}
+
+/*
+ * Up to `limit` table ids following `start_after` in the index, from the first one without it.
+ * None when `start_after` is not a stored table.
+ */
+pub fn table_ids(storage: &dyn Storage, start_after: Option<u32>, limit: u32) -> StdResult<Option<Vec<u32>>> {
+    let start = match start_after {
+        Some(table_id) => match TABLE_POSITIONS.get(storage, &table_id) {
+            Some(position) => position + 1,
+            None => return Ok(None),
+        },
+        None => 0,
+    };
+    let index = table_index();
+    let end = index.get_len(storage)?.min(start.saturating_add(limit));
+    (start..end)
+        .map(|position| index.get_at(storage, position))
+        .collect::<StdResult<Vec<u32>>>()
+        .map(Some)
+}
+
 pub fn save_table(storage: &mut dyn Storage, key: u32, item: &PokerTable) -> StdResult<()> {
+    if !TABLE_POSITIONS.contains(storage, &key) {
+        let index = table_index();
+        let position = index.get_len(storage)?;
+        TABLE_POSITIONS.insert(storage, &key, &position)?;
+        index.push(storage, &key)?;
+        TABLE_OPERATORS.insert(storage, &key, &item.operator)?;
+    }
     TABLES_STORE.insert(storage, &key, item).map_err(|err| {
         StdError::generic_err(format!("Failed to save table: {}", err))
     })
@@ -30,16 +183,49 @@ pub fn load_table(storage: &dyn Storage, key: u32) -> Option<PokerTable> {
 }
 
 pub fn delete_table(storage: &mut dyn Storage, key: u32) -> StdResult<()> {
+    if let Some(position) = TABLE_POSITIONS.get(storage, &key) {
+        let index = table_index();
+        let last = index.pop(storage)?;
+        if last != key {
+            index.set_at(storage, position, &last)?;
+            TABLE_POSITIONS.insert(storage, &last, &position)?;
+        }
+        TABLE_POSITIONS.remove(storage, &key)?;
+    }
     TABLES_STORE.remove(storage, &key).map_err(|err| {
         StdError::generic_err(format!("Failed to delete table: {}", err))
     })
 }
 
+/*
+ * Logs of the finished hands keyed by hand_ref, one keymap per table (suffixed with the table_id).
+ * A hand is archived once the next one replaces it on the table, and never rewritten. The history outlives
+ * a closed table, the hand_refs keep increasing when it reopens so the keymap stays sorted by hand_ref.
+ */
+pub static HAND_HISTORY: Keymap<u32, LastHandLogResponse, Json> = KeymapBuilder::new(b"hand_history").build();
+
+pub fn hand_history(table_id: u32) -> Keymap<'static, u32, LastHandLogResponse, Json> {
+    HAND_HISTORY.add_suffix(&table_id.to_be_bytes())
+}
+
+pub fn last_archived_hand(storage: &dyn Storage, table_id: u32) -> StdResult<Option<u32>> {
+    hand_history(table_id).iter_keys(storage)?.next_back().transpose()
+}
+
+pub fn archive_hand(storage: &mut dyn Storage, table_id: u32, hand_log: &LastHandLogResponse) -> StdResult<()> {
+    let history = hand_history(table_id);
+    if history.contains(storage, &hand_log.hand_ref) {
+        return Ok(());
+    }
+    history.insert(storage, &hand_log.hand_ref, hand_log)
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct CommunityCards {
     pub flop: Flop,
     pub turn: Turn, 
     pub river: River, 
+    pub burned: Vec<Card>, // burned before the flop, turn and river when enabled, only revealed in the next hand's log
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -47,6 +233,7 @@ pub struct Flop {
     pub cards: Vec<Card>,
     pub secret: u64,
     pub retrieved_at: Option<Timestamp>,
+    pub submitted_shares: Vec<(u32, u64)>, // (share_index, share) sent by the players through SubmitShare
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -54,6 +241,7 @@ pub struct Turn {
     pub card: Card,
     pub secret: u64,
     pub retrieved_at: Option<Timestamp>,
+    pub submitted_shares: Vec<(u32, u64)>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -61,14 +249,62 @@ pub struct River {
     pub card: Card,
     pub secret: u64,
     pub retrieved_at: Option<Timestamp>,
+    pub submitted_shares: Vec<(u32, u64)>,
+}
+
+impl CommunityCards {
+    pub fn secret(&self, game_state: &GameState) -> Option<u64> {
+        match game_state {
+            GameState::Flop => Some(self.flop.secret),
+            GameState::Turn => Some(self.turn.secret),
+            GameState::River => Some(self.river.secret),
+            _ => None,
+        }
+    }
+
+    pub fn submitted_shares_mut(&mut self, game_state: &GameState) -> Option<&mut Vec<(u32, u64)>> {
+        match game_state {
+            GameState::Flop => Some(&mut self.flop.submitted_shares),
+            GameState::Turn => Some(&mut self.turn.submitted_shares),
+            GameState::River => Some(&mut self.river.submitted_shares),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct PokerTable {
+    pub operator: Addr, // set when the table is created, only they and the owner run its hands
     pub hand_ref: u32,
+    pub game_state: GameState,
     pub players: Vec<Player>,
     pub community_cards: CommunityCards,
+    pub deck: Deck, // shuffled deck before dealing, revealed in the next hand's log
+    pub deck_salt: Binary,
+    pub secret_sharing: SecretSharing,
+    pub game_variant: GameVariant,
+    pub deck_type: DeckType,
+    pub extra_runs: Vec<Vec<Card>>, // boards 2..N of a run-it-multiple all-in, dealt from the rest of the deck
+    pub betting: Option<Betting>, // None when the betting is handled off-chain
+    pub rake: Uint128, // taken at the end of the hand
+    pub started_at: Timestamp,
     pub showdown_retrieved_at: Option<Timestamp>,
+    pub void_reason: Option<String>, // set when the hand was voided, nothing can be dealt from it anymore
+}
+
+impl PokerTable {
+    // the hand start or its latest reveal
+    pub fn last_activity(&self) -> Timestamp {
+        [
+            self.community_cards.flop.retrieved_at,
+            self.community_cards.turn.retrieved_at,
+            self.community_cards.river.retrieved_at,
+            self.showdown_retrieved_at,
+        ]
+        .into_iter()
+        .flatten()
+        .fold(self.started_at, Timestamp::max)
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -76,14 +312,96 @@ pub struct Player {
     pub username: String,
     pub player_id: Uuid,
     pub public_key: String,
+    pub address: Addr, // the public key validated as an address, signs the player's own messages
     pub hand: Vec<Card>,
     pub hand_secret: u64,
+    pub share_index: u32, // 1-based x coordinate of the player's community secret shares
     pub flop_secret_share: u64,
     pub turn_secret_share: u64,
     pub river_secret_share: u64,
+    pub stack: Uint128, // chips behind, not counting the ones committed to the pot
+    pub street_bet: Uint128, // committed on the current street
+    pub total_bet: Uint128, // committed over the whole hand
+    pub folded: bool,
+    pub all_in: bool,
+    pub acted: bool, // acted since the last bet or raise of the street
 }
 
+/*
+ * No-limit betting state of a hand. Players are seated in the StartGame order, the blinds
+ * are posted left of the button and the pot is the sum of every player's total_bet.
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Betting {
+    pub small_blind: Uint128,
+    pub big_blind: Uint128,
+    pub button: usize,
+    pub current_bet: Uint128, // highest street_bet, the amount to call
+    pub min_raise: Uint128, // size of the last full bet or raise of the street
+    pub to_act: Option<usize>, // None once no more betting is possible on the street
+    pub escrow: Option<Currency>, // stacks come from the escrowed balances and the winnings are paid out in that currency
+    pub actions: Vec<ActionRecord>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ActionRecord {
+    pub player_id: Uuid,
+    pub game_state: GameState,
+    pub action: PlayerAction,
+    pub amount: Uint128, // chips put in the pot by the action
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayerAction {
+    SmallBlind,
+    BigBlind,
+    Fold,
+    Check,
+    Call,
+    Bet,
+    Raise, // to a total street bet
+    AllIn,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretSharing {
+    Additive, // n-of-n, the secret is the wrapping sum of every player's share
+    Shamir { threshold: u32 }, // k-of-n over the prime field of crate::shamir
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GameVariant {
+    #[default]
+    HoldEm,
+    Omaha4,
+    Omaha5,
+    Omaha8, // four-card Omaha Hi-Lo, the pot is split with the best 8-or-better low
+}
+
+impl GameVariant {
+    pub fn hole_cards(&self) -> usize {
+        match self {
+            GameVariant::HoldEm => 2,
+            GameVariant::Omaha4 | GameVariant::Omaha8 => 4,
+            GameVariant::Omaha5 => 5,
+        }
+    }
+
+    pub fn is_hi_lo(&self) -> bool {
+        matches!(self, GameVariant::Omaha8)
+    }
+}
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DeckType {
+    #[default]
+    Standard,
+    ShortDeck, // 6+ Hold'em, the 2s to 5s are removed
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
@@ -92,6 +410,35 @@ pub enum GameState {
     Flop,
     Turn,
     River,
+    Showdown,
+    Closed,
+}
+
+impl GameState {
+    /*
+     * A hand moves strictly forward: PreFlop -> Flop -> Turn -> River -> Showdown -> Closed.
+     * Showdown can also be reached early from any street when players are all-in, which is why
+     * the showdown handler does not go through `next` but checks the street it was called from.
+     * The pots are settled with the showdown (or when everybody else folds), so a finished hand is
+     * stored Closed, like a voided one, and only a Closed hand is replaced without forcing.
+     */
+    pub fn next(&self) -> Option<GameState> {
+        match self {
+            GameState::PreFlop => Some(GameState::Flop),
+            GameState::Flop => Some(GameState::Turn),
+            GameState::Turn => Some(GameState::River),
+            GameState::River => Some(GameState::Showdown),
+            GameState::Showdown => Some(GameState::Closed),
+            GameState::Closed => None,
+        }
+    }
+
+    pub fn is_street(&self) -> bool {
+        matches!(
+            self,
+            GameState::PreFlop | GameState::Flop | GameState::Turn | GameState::River
+        )
+    }
 }
 
 
@@ -101,7 +448,7 @@ pub struct Card(u8);
 impl Card {
     pub fn new(suit: u8, rank: u8) -> Self {
         assert!(suit < 4, "Invalid suit");
-        assert!(rank >= 1 && rank <= 13, "Invalid rank");
+        assert!((1..=13).contains(&rank), "Invalid rank");
         Card((suit << 4) | rank)
     }
 
@@ -120,8 +467,10 @@ impl Card {
     pub fn from_bytes(byte: u8) -> Self {
         Card(byte)
     }
+}
 
-    pub fn to_string(&self) -> String {
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         /* Order of suits in this list is relatively important (as they are mostly continuous digits, ranks is pretty hard to f*** up...),
          * this list of suits should be in the same order in the backend and frontend executing/querying the contract.
          * This order is crucial because the contract logs the cards from the last game 
@@ -131,7 +480,7 @@ impl Card {
          */ 
         let suits = ["♣", "♦", "♥", "♠"]; 
         let ranks = ["A", "2", "3", "4", "5", "6", "7", "8", "9", "10", "J", "Q", "K"];
-        format!("{}{}", suits[self.suit() as usize], ranks[self.rank() as usize - 1])
+        write!(f, "{}{}", suits[self.suit() as usize], ranks[self.rank() as usize - 1])
     }
 }
 
@@ -141,17 +490,38 @@ pub struct Deck {
     pub cards: Vec<Card>,
 }
 
+impl Default for Deck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Deck {
     pub fn new() -> Self {
+        Self::with_ranks(1..=13)
+    }
+
+    pub fn short_deck() -> Self {
+        Self::with_ranks(std::iter::once(1).chain(6..=13))
+    }
+
+    pub fn with_ranks(ranks: impl IntoIterator<Item = u8> + Clone) -> Self {
         let mut cards = Vec::new();
         for suit in 0..4 {
-            for rank in 1..=13 {
+            for rank in ranks.clone() {
                 cards.push(Card::new(suit, rank));
             }
         }
         Deck { cards }
     }
 
+    pub fn from_type(deck_type: &DeckType) -> Self {
+        match deck_type {
+            DeckType::Standard => Self::new(),
+            DeckType::ShortDeck => Self::short_deck(),
+        }
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         self.cards.iter().map(|card| card.0).collect()
     }
@@ -160,6 +530,17 @@ impl Deck {
         let cards = bytes.iter().map(|&b| Card(b)).collect();
         Deck { cards }
     }
+
+    /*
+     * SHA-256(salt || deck bytes). Published in plaintext when the hand starts, the salt and the deck
+     * are published in the next hand's log so anyone can recompute it and check the dealt cards.
+     */
+    pub fn commitment(&self, salt: &[u8]) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        hasher.update(salt);
+        hasher.update(self.to_bytes());
+        hasher.finalize().to_vec()
+    }
 }
 
 
@@ -172,7 +553,15 @@ use super::*;
         let deck = Deck::new();
         for card in deck.cards.iter() {
             println!("{}", card.to_bytes());
-            println!("{}", card.to_string());
+            println!("{}", card);
         }
     }
+
+    #[test]
+    fn short_deck() {
+        let deck = Deck::short_deck();
+        assert_eq!(deck.cards.len(), 36);
+        assert!(deck.cards.iter().all(|card| card.rank() == 1 || card.rank() >= 6));
+        assert_eq!(Deck::from_type(&DeckType::Standard), Deck::new());
+    }
 }
\ No newline at end of file